@@ -0,0 +1,344 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use audio_utils::resample::Resampler;
+use tracing::{debug, warn};
+
+use crate::{recording::Recording, Result, Transcibe, TranscriptionError, TranscriptionResult};
+
+/// A recording and what was said in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    name: String,
+    audio: PathBuf,
+    reference: String,
+}
+
+impl Sample {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn audio(&self) -> &Path {
+        &self.audio
+    }
+
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+}
+
+/// WAV files in a directory, each with its reference transcript beside it in a `.txt` file of the
+/// same name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Corpus {
+    samples: Vec<Sample>,
+}
+
+impl Corpus {
+    /// Recordings without a transcript are left out with a warning
+    pub fn load(directory: impl AsRef<Path>) -> Result<Self> {
+        let directory = directory.as_ref();
+        let corpus_error =
+            |f: std::io::Error| TranscriptionError::Audio(format!("{}: {f}", directory.display()));
+        let mut samples = Vec::new();
+        for entry in std::fs::read_dir(directory).map_err(corpus_error)? {
+            let audio = entry.map_err(corpus_error)?.path();
+            if !audio
+                .extension()
+                .is_some_and(|f| f.eq_ignore_ascii_case("wav"))
+            {
+                continue;
+            }
+            let transcript = audio.with_extension("txt");
+            match std::fs::read_to_string(&transcript) {
+                Ok(reference) => samples.push(Sample {
+                    name: audio
+                        .file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned(),
+                    audio,
+                    reference: reference.trim().to_owned(),
+                }),
+                Err(e) => warn!(recording = ?audio, "left out of the corpus: {e}"),
+            }
+        }
+        samples.sort_by(|a, b| a.name.cmp(&b.name));
+        debug!(count = samples.len(), "corpus loaded");
+        Ok(Self { samples })
+    }
+
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+/// How one backend did on one sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleScore {
+    name: String,
+    hypothesis: String,
+    word_errors: usize,
+    reference_words: usize,
+    character_errors: usize,
+    reference_characters: usize,
+    audio: Duration,
+    processing: Duration,
+    latency: Duration,
+    failed: bool,
+}
+
+impl SampleScore {
+    // a sample that could not be transcribed at all, every word of it was missed
+    fn unheard(sample: &Sample, audio: Duration) -> Self {
+        let reference_words = normalise(&sample.reference);
+        let reference_characters = reference_words.join(" ").chars().count();
+        Self {
+            name: sample.name.clone(),
+            hypothesis: String::new(),
+            word_errors: reference_words.len(),
+            reference_words: reference_words.len(),
+            character_errors: reference_characters,
+            reference_characters,
+            audio,
+            processing: Duration::ZERO,
+            latency: Duration::ZERO,
+            failed: true,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Every finalised result joined together
+    pub fn hypothesis(&self) -> &str {
+        &self.hypothesis
+    }
+
+    pub fn word_error_rate(&self) -> f32 {
+        rate(self.word_errors, self.reference_words)
+    }
+
+    pub fn character_error_rate(&self) -> f32 {
+        rate(self.character_errors, self.reference_characters)
+    }
+
+    /// Time spent transcribing over the length of the audio
+    pub fn real_time_factor(&self) -> f32 {
+        self.processing.as_secs_f32() / self.audio.as_secs_f32().max(f32::EPSILON)
+    }
+
+    /// From the last of the audio being handed over to the utterance being finalised
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Whether the recording could not be read or the backend returned an error, what it heard
+    /// up to then is still scored
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+}
+
+/// How one backend did across a corpus. Rates are over the whole corpus rather than averages of
+/// the samples, so longer samples count for more
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    source: String,
+    samples: Vec<SampleScore>,
+}
+
+impl Evaluation {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn samples(&self) -> &[SampleScore] {
+        &self.samples
+    }
+
+    pub fn word_error_rate(&self) -> f32 {
+        rate(
+            self.samples.iter().map(|f| f.word_errors).sum(),
+            self.samples.iter().map(|f| f.reference_words).sum(),
+        )
+    }
+
+    pub fn character_error_rate(&self) -> f32 {
+        rate(
+            self.samples.iter().map(|f| f.character_errors).sum(),
+            self.samples.iter().map(|f| f.reference_characters).sum(),
+        )
+    }
+
+    pub fn real_time_factor(&self) -> f32 {
+        let processing: Duration = self.samples.iter().map(|f| f.processing).sum();
+        let audio: Duration = self.samples.iter().map(|f| f.audio).sum();
+        processing.as_secs_f32() / audio.as_secs_f32().max(f32::EPSILON)
+    }
+
+    pub fn mean_latency(&self) -> Duration {
+        let total: Duration = self.samples.iter().map(|f| f.latency).sum();
+        total
+            .checked_div(self.samples.len() as u32)
+            .unwrap_or_default()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.samples.iter().filter(|f| f.failed).count()
+    }
+}
+
+/// Runs every sample of the corpus through a backend as one utterance. `backend` is called for
+/// each sample rate the corpus has, recordings of the same rate share one backend. A recording
+/// that cannot be read, or has no backend for its rate, is scored as failed and the run goes on
+pub fn evaluate(
+    corpus: &Corpus,
+    mut backend: impl FnMut(f32) -> Result<Box<dyn Transcibe>>,
+) -> Evaluation {
+    // the backend for the current sample rate, `None` if it could not be created
+    let mut current: Option<(f32, Option<Box<dyn Transcibe>>)> = None;
+    let mut source = String::new();
+    let mut samples = Vec::with_capacity(corpus.samples.len());
+    for sample in &corpus.samples {
+        let recording = match Recording::open_wav(&sample.audio) {
+            Ok(recording) => recording,
+            Err(e) => {
+                warn!(sample = sample.name, "{e}");
+                samples.push(SampleScore::unheard(sample, Duration::ZERO));
+                continue;
+            }
+        };
+        let sample_rate = recording.sample_rate();
+        if current.as_ref().map(|(f, _)| *f) != Some(sample_rate) {
+            let transcriber = backend(sample_rate)
+                .map_err(|e| warn!(sample_rate, "{e}"))
+                .ok();
+            if let Some(transcriber) = &transcriber {
+                source = transcriber.source().to_owned();
+            }
+            current = Some((sample_rate, transcriber));
+        }
+        let Some((_, Some(transcriber))) = &current else {
+            samples.push(SampleScore::unheard(sample, recording.duration()));
+            continue;
+        };
+        let score = score(sample, &recording, transcriber.as_ref());
+        debug!(
+            source = transcriber.source(),
+            sample = sample.name,
+            wer = score.word_error_rate(),
+            "sample evaluated"
+        );
+        samples.push(score);
+    }
+    Evaluation { source, samples }
+}
+
+fn score(sample: &Sample, recording: &Recording, transcriber: &dyn Transcibe) -> SampleScore {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    // about as much as a microphone hands over at a time
+    let chunk = (recording.sample_rate() / 10.0).max(1.0) as usize;
+    let mut resampler = transcriber
+        .sample_rate()
+        .filter(|f| *f != recording.sample_rate())
+        .map(|f| Resampler::new(recording.sample_rate(), f));
+    let mut failed = false;
+    let mut fail = |e: TranscriptionError| {
+        warn!(source = transcriber.source(), sample = sample.name, "{e}");
+        failed = true;
+    };
+
+    let started = Instant::now();
+    if let Err(e) = transcriber
+        .reset()
+        .and_then(|_| transcriber.begin_utterance())
+    {
+        fail(e);
+    }
+    for samples in recording.samples().chunks(chunk) {
+        let result = match &mut resampler {
+            Some(resampler) => transcriber.transcribe(&resampler.process_i16(samples), &tx),
+            None => transcriber.transcribe(samples, &tx),
+        };
+        if let Err(e) = result {
+            fail(e);
+            break;
+        }
+    }
+    let fed = Instant::now();
+    if let Err(e) = transcriber.end_utterance(&tx) {
+        fail(e);
+    }
+    let finished = Instant::now();
+
+    let hypothesis = rx
+        .try_iter()
+        .filter(TranscriptionResult::finalised)
+        .map(|f| f.transcription().trim().to_owned())
+        .filter(|f| !f.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let reference_words = normalise(&sample.reference);
+    let hypothesis_words = normalise(&hypothesis);
+    let reference_characters: Vec<char> = reference_words.join(" ").chars().collect();
+    let hypothesis_characters: Vec<char> = hypothesis_words.join(" ").chars().collect();
+    SampleScore {
+        name: sample.name.clone(),
+        word_errors: edit_distance(&reference_words, &hypothesis_words),
+        reference_words: reference_words.len(),
+        character_errors: edit_distance(&reference_characters, &hypothesis_characters),
+        reference_characters: reference_characters.len(),
+        hypothesis,
+        audio: recording.duration(),
+        processing: finished - started,
+        latency: finished - fed,
+        failed,
+    }
+}
+
+/// Lowercase words with punctuation removed, apostrophes are kept
+pub fn normalise(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|f: char| !f.is_alphanumeric() && f != '\'')
+        .map(|f| f.trim_matches('\''))
+        .filter(|f| !f.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Substitutions, deletions and insertions needed to turn `reference` into `hypothesis`
+pub fn edit_distance<T: PartialEq>(reference: &[T], hypothesis: &[T]) -> usize {
+    let mut previous: Vec<usize> = (0..=hypothesis.len()).collect();
+    let mut current = vec![0; hypothesis.len() + 1];
+    for (i, expected) in reference.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, heard) in hypothesis.iter().enumerate() {
+            let substitution = previous[j] + usize::from(expected != heard);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[hypothesis.len()]
+}
+
+// an empty reference only scores zero when nothing was heard
+fn rate(errors: usize, length: usize) -> f32 {
+    if length == 0 {
+        if errors == 0 {
+            0.0
+        } else {
+            1.0
+        }
+    } else {
+        errors as f32 / length as f32
+    }
+}
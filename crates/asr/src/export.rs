@@ -0,0 +1,241 @@
+use std::{fmt::Display, str::FromStr, time::UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::{history::HistoryEntry, TranscriptionResult, Word};
+
+// how long an utterance without word timings is assumed to take, about 150 words a minute
+const SECONDS_PER_WORD: f32 = 0.4;
+
+// the usual limit for a line of subtitles
+const LINE_LENGTH: usize = 42;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// SubRip subtitles
+    Srt,
+    /// WebVTT captions, as browsers take them
+    WebVtt,
+    /// Segments with their word timings
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "srt" => Ok(Self::Srt),
+            "vtt" | "webvtt" => Ok(Self::WebVtt),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("{s:?} is not one of srt, vtt or json")),
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ExportFormat::Srt => "srt",
+            ExportFormat::WebVtt => "vtt",
+            ExportFormat::Json => "json",
+        })
+    }
+}
+
+/// A finalised utterance placed on a timeline, in seconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Segment {
+    start: f32,
+    end: f32,
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speaker: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    words: Vec<Word>,
+}
+
+impl Segment {
+    pub fn start(&self) -> f32 {
+        self.start
+    }
+
+    pub fn end(&self) -> f32 {
+        self.end
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn speaker(&self) -> Option<&str> {
+        self.speaker.as_deref()
+    }
+
+    pub fn words(&self) -> &[Word] {
+        &self.words
+    }
+
+    /// Segments of one transcribed stream, timed by their words. Results the backend did not
+    /// time follow on from the segment before them
+    pub fn from_results(results: &[TranscriptionResult]) -> Vec<Self> {
+        let mut segments: Vec<Self> = Vec::new();
+        for result in results
+            .iter()
+            .filter(|f| f.finalised() && !f.transcription().trim().is_empty())
+        {
+            let (start, end) = match (result.words().first(), result.words().last()) {
+                (Some(first), Some(last)) => (first.start, last.end.max(first.start)),
+                _ => {
+                    let start = segments.last().map(|f| f.end).unwrap_or_default();
+                    (start, start + estimated_duration(result.transcription()))
+                }
+            };
+            segments.push(Self {
+                start,
+                end,
+                text: result.transcription().to_owned(),
+                speaker: result.speaker().map(str::to_owned),
+                words: result.words().to_vec(),
+            });
+        }
+        segments
+    }
+
+    /// Segments of stored transcripts placed by when they were recorded, starting from the first
+    pub fn from_history(entries: &[HistoryEntry]) -> Vec<Self> {
+        // entries are stored when the utterance is finalised, so that is where it ends
+        let timed: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                let end = entry
+                    .timestamp()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                let duration = match (entry.words().first(), entry.words().last()) {
+                    (Some(first), Some(last)) => (last.end - first.start).max(0.0),
+                    _ => estimated_duration(entry.text()),
+                };
+                (end - f64::from(duration), duration, entry)
+            })
+            .collect();
+        let origin = timed
+            .iter()
+            .map(|(start, ..)| *start)
+            .reduce(f64::min)
+            .unwrap_or_default();
+
+        let mut segments: Vec<Self> = timed
+            .into_iter()
+            .map(|(start, duration, entry)| {
+                let start = (start - origin) as f32;
+                // word timings are relative to the backend's stream, move them onto this timeline
+                let shift = start - entry.words().first().map(|f| f.start).unwrap_or_default();
+                let words = entry
+                    .words()
+                    .iter()
+                    .map(|f| Word::new(&f.word, f.start + shift, f.end + shift, f.confidence))
+                    .collect();
+                Self {
+                    start,
+                    end: start + duration,
+                    text: entry.text().to_owned(),
+                    speaker: entry.speaker().map(str::to_owned),
+                    words,
+                }
+            })
+            .collect();
+        segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+        segments
+    }
+}
+
+fn estimated_duration(text: &str) -> f32 {
+    text.split_whitespace().count().max(1) as f32 * SECONDS_PER_WORD
+}
+
+pub fn export(segments: &[Segment], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Srt => srt(segments),
+        ExportFormat::WebVtt => web_vtt(segments),
+        ExportFormat::Json => {
+            #[derive(Serialize)]
+            struct Document<'a> {
+                segments: &'a [Segment],
+            }
+            let mut json = serde_json::to_string_pretty(&Document { segments })
+                .expect("segments are always serialisable");
+            json.push('\n');
+            json
+        }
+    }
+}
+
+fn srt(segments: &[Segment]) -> String {
+    let mut srt = String::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let mut text = String::new();
+        if let Some(speaker) = &segment.speaker {
+            text.push_str(&format!("{speaker}: "));
+        }
+        text.push_str(&segment.text);
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            timestamp(segment.start, ','),
+            timestamp(segment.end, ','),
+            wrap(&text)
+        ));
+    }
+    srt
+}
+
+fn web_vtt(segments: &[Segment]) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for segment in segments {
+        let text = wrap(&escape(&segment.text));
+        let text = match &segment.speaker {
+            Some(speaker) => format!("<v {}>{text}", escape(speaker)),
+            None => text,
+        };
+        vtt.push_str(&format!(
+            "{} --> {}\n{text}\n\n",
+            timestamp(segment.start, '.'),
+            timestamp(segment.end, '.')
+        ));
+    }
+    vtt
+}
+
+// hh:mm:ss followed by milliseconds
+fn timestamp(seconds: f32, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+fn wrap(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.len() + 1 + word.len() <= LINE_LENGTH => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_owned()),
+        }
+    }
+    lines.join("\n")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,296 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{Result, TranscriptionError, TranscriptionResult, Word};
+
+/// A finalised utterance as it was stored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    id: u64,
+    session: String,
+    timestamp: u64,
+    source: String,
+    text: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confidence: Option<f32>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speaker: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    words: Vec<Word>,
+}
+
+impl HistoryEntry {
+    /// Increases with every entry recorded, deleted ids are not reused
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Identifies the run of kara that recorded this
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    pub fn timestamp(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.timestamp)
+    }
+
+    /// The backend that produced the transcription
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn confidence(&self) -> Option<f32> {
+        self.confidence
+    }
+
+    pub fn speaker(&self) -> Option<&str> {
+        self.speaker.as_deref()
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// Word timings as the backend reported them, relative to its own stream
+    pub fn words(&self) -> &[Word] {
+        &self.words
+    }
+}
+
+/// Selects entries for [`History::search`] and [`History::delete`]. Every field that is set has
+/// to match, the default matches everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryQuery {
+    /// Every word has to appear in the text, ignoring case
+    pub text: Option<String>,
+    /// Recorded at or after
+    pub since: Option<SystemTime>,
+    /// Recorded before
+    pub until: Option<SystemTime>,
+    pub session: Option<String>,
+    pub source: Option<String>,
+    /// Keeps only the most recent matches
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        let timestamp = entry.timestamp();
+        self.since.is_none_or(|f| timestamp >= f)
+            && self.until.is_none_or(|f| timestamp < f)
+            && self.session.as_ref().is_none_or(|f| *f == entry.session)
+            && self.source.as_ref().is_none_or(|f| *f == entry.source)
+            && self.text.as_ref().is_none_or(|f| {
+                let text = entry.text.to_lowercase();
+                f.split_whitespace()
+                    .all(|term| text.contains(&term.to_lowercase()))
+            })
+    }
+}
+
+/// How long transcripts are kept. Both limits apply when both are set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Retention {
+    /// Entries older than this are removed, kept forever when unset
+    #[serde(rename = "max-age-days")]
+    #[serde(default = "max_age_days")]
+    pub max_age_days: Option<u32>,
+
+    /// Only the most recent entries are kept, no limit when unset
+    #[serde(rename = "max-entries")]
+    #[serde(default)]
+    pub max_entries: Option<usize>,
+}
+
+fn max_age_days() -> Option<u32> {
+    Some(30)
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            max_age_days: max_age_days(),
+            max_entries: None,
+        }
+    }
+}
+
+/// Finalised transcripts appended to a JSON lines file, one entry per line
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+    session: String,
+    next_id: u64,
+}
+
+impl History {
+    /// Opens the history at `path`, creating it when it is first recorded to. Every call starts a
+    /// new session
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let next_id = read_entries(&path)?
+            .iter()
+            .map(|f| f.id + 1)
+            .max()
+            .unwrap_or_default();
+        let session = format!("{:x}-{:x}", now(), std::process::id());
+        debug!(path = ?path, session, "transcript history opened");
+        Ok(Self {
+            path,
+            session,
+            next_id,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    /// Appends a finalised result. Partials and empty results are not recorded
+    pub fn record(&mut self, result: &TranscriptionResult) -> Result<Option<HistoryEntry>> {
+        if !result.finalised() || result.transcription().trim().is_empty() {
+            return Ok(None);
+        }
+        let entry = HistoryEntry {
+            id: self.next_id,
+            session: self.session.clone(),
+            timestamp: now(),
+            source: result.source().unwrap_or("unknown").to_owned(),
+            text: result.transcription().to_owned(),
+            confidence: result.confidence(),
+            speaker: result.speaker().map(str::to_owned),
+            language: result.language().map(str::to_owned),
+            words: result.words().to_vec(),
+        };
+        if let Some(directory) = self.path.parent() {
+            std::fs::create_dir_all(directory).map_err(history_error(directory))?;
+        }
+        let mut line = serde_json::to_string(&entry)
+            .map_err(|f| TranscriptionError::History(f.to_string()))?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| f.write_all(line.as_bytes()))
+            .map_err(history_error(&self.path))?;
+        self.next_id += 1;
+        Ok(Some(entry))
+    }
+
+    /// Matching entries, oldest first
+    pub fn search(&self, query: &HistoryQuery) -> Result<Vec<HistoryEntry>> {
+        let mut entries: Vec<_> = read_entries(&self.path)?
+            .into_iter()
+            .filter(|f| query.matches(f))
+            .collect();
+        if let Some(limit) = query.limit {
+            entries.drain(..entries.len().saturating_sub(limit));
+        }
+        Ok(entries)
+    }
+
+    /// Removes the matching entries, returning how many there were. The limit of the query is
+    /// ignored
+    pub fn delete(&mut self, query: &HistoryQuery) -> Result<usize> {
+        let entries = read_entries(&self.path)?;
+        let count = entries.len();
+        let kept: Vec<_> = entries.into_iter().filter(|f| !query.matches(f)).collect();
+        let removed = count - kept.len();
+        if removed > 0 {
+            self.rewrite(&kept)?;
+        }
+        Ok(removed)
+    }
+
+    /// Removes whatever the retention policy no longer allows, returning how many entries went
+    pub fn apply_retention(&mut self, retention: &Retention) -> Result<usize> {
+        let entries = read_entries(&self.path)?;
+        let count = entries.len();
+        let oldest = retention
+            .max_age_days
+            .map(|f| now().saturating_sub(u64::from(f) * 24 * 60 * 60 * 1000));
+        let mut kept: Vec<_> = entries
+            .into_iter()
+            .filter(|f| oldest.is_none_or(|oldest| f.timestamp >= oldest))
+            .collect();
+        if let Some(max_entries) = retention.max_entries {
+            kept.drain(..kept.len().saturating_sub(max_entries));
+        }
+        let removed = count - kept.len();
+        if removed > 0 {
+            self.rewrite(&kept)?;
+            debug!(removed, "transcript history trimmed");
+        }
+        Ok(removed)
+    }
+
+    // written beside the history and renamed over it so an interrupted write loses nothing
+    fn rewrite(&self, entries: &[HistoryEntry]) -> Result<()> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        let file = File::create(&temporary).map_err(history_error(&temporary))?;
+        let mut writer = BufWriter::new(file);
+        for entry in entries {
+            serde_json::to_writer(&mut writer, entry)
+                .map_err(|f| TranscriptionError::History(f.to_string()))?;
+            writer.write_all(b"\n").map_err(history_error(&temporary))?;
+        }
+        writer.flush().map_err(history_error(&temporary))?;
+        drop(writer);
+        std::fs::rename(&temporary, &self.path).map_err(history_error(&self.path))
+    }
+}
+
+// a missing file is an empty history, lines that cannot be read are skipped
+fn read_entries(path: &Path) -> Result<Vec<HistoryEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let file = File::open(path).map_err(history_error(path))?;
+    let mut entries = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(history_error(path))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!(path = ?path, line = number + 1, "history entry skipped: {e}"),
+        }
+    }
+    Ok(entries)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn history_error(path: &Path) -> impl Fn(std::io::Error) -> TranscriptionError + '_ {
+    move |f| TranscriptionError::History(format!("{}: {f}", path.display()))
+}
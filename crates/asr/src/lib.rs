@@ -1,14 +1,110 @@
+pub mod evaluation;
+pub mod export;
+pub mod history;
+pub mod recording;
 pub mod sources;
+pub mod speaker;
+pub mod text;
+pub mod vocabulary;
+
+#[cfg(test)]
+mod tests;
 
 pub trait Transcibe: Send {
     fn source(&self) -> &str;
     fn transcribe(&self, stream: &[i16], result_sender: &Sender<TranscriptionResult>)
         -> Result<()>;
+
+    /// The rate audio has to be fed at, `None` takes the stream as it comes
+    fn sample_rate(&self) -> Option<f32> {
+        None
+    }
+
+    /// Starts a fresh utterance, dropping anything left over from the previous one
+    fn begin_utterance(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Marks the end of the current utterance. Whatever the backend still holds is sent as a
+    /// finalised result
+    fn end_utterance(&self, _result_sender: &Sender<TranscriptionResult>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Discards buffered audio and partial hypotheses without producing a result
+    fn reset(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Switches between open dictation and a constrained command grammar
+    fn set_mode(&self, _mode: &RecognitionMode) -> Result<()> {
+        Err(TranscriptionError::Unsupported(format!(
+            "{} cannot change its recognition mode",
+            self.source()
+        )))
+    }
+
+    /// Takes custom words and phrases, as runtime hints where the backend has them and by
+    /// rescoring and rewriting its results
+    fn set_vocabulary(&self, _vocabulary: &Vocabulary) -> Result<()> {
+        Err(TranscriptionError::Unsupported(format!(
+            "{} cannot use a custom vocabulary",
+            self.source()
+        )))
+    }
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum RecognitionMode {
+    /// Open vocabulary recognition
+    #[default]
+    Dictation,
+    /// Only the listed phrases can be recognised
+    Command(Vec<String>),
+}
+
+impl RecognitionMode {
+    /// Reads a grammar file: either a JSON array of phrases, as vosk takes them, or one
+    /// phrase per line with `#` starting a comment
+    pub fn from_grammar_file(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|f| TranscriptionError::Grammar(format!("{}: {f}", path.display())))?;
+        Self::from_grammar(&contents)
+            .map_err(|f| TranscriptionError::Grammar(format!("{}: {f}", path.display())))
+    }
+
+    fn from_grammar(contents: &str) -> std::result::Result<Self, String> {
+        let phrases: Vec<String> = if contents.trim_start().starts_with('[') {
+            serde_json::from_str(contents).map_err(|f| f.to_string())?
+        } else {
+            contents
+                .lines()
+                .map(|f| f.split('#').next().unwrap_or_default().trim())
+                .filter(|f| !f.is_empty())
+                .map(str::to_owned)
+                .collect()
+        };
+        if phrases.is_empty() {
+            Err(String::from("grammar has no phrases"))
+        } else {
+            Ok(Self::Command(phrases))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct TranscriptionResult {
     text: String,
     finalised: bool,
+    words: Vec<Word>,
+    alternatives: Vec<Alternative>,
+    // what the backend made of the best hypothesis as a whole
+    score: Option<f32>,
+    speaker: Option<String>,
+    speaker_embedding: Option<Vec<f32>>,
+    language: Option<String>,
+    source: Option<String>,
 }
 
 impl TranscriptionResult {
@@ -16,9 +112,47 @@ impl TranscriptionResult {
         Self {
             text: text.to_string(),
             finalised,
+            words: Vec::new(),
+            alternatives: Vec::new(),
+            score: None,
+            speaker: None,
+            speaker_embedding: None,
+            language: None,
+            source: None,
         }
     }
 
+    fn with_words(mut self, words: Vec<Word>) -> Self {
+        self.words = words;
+        self
+    }
+
+    fn with_alternatives(mut self, alternatives: Vec<Alternative>) -> Self {
+        self.alternatives = alternatives;
+        self
+    }
+
+    fn with_score(mut self, score: f32) -> Self {
+        self.score = Some(score);
+        self
+    }
+
+    fn with_speaker(mut self, embedding: Vec<f32>, speaker: Option<String>) -> Self {
+        self.speaker_embedding = Some(embedding);
+        self.speaker = speaker;
+        self
+    }
+
+    fn with_language(mut self, language: &str) -> Self {
+        self.language = Some(language.to_owned());
+        self
+    }
+
+    fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_owned());
+        self
+    }
+
     pub fn transcription(&self) -> &str {
         &self.text
     }
@@ -26,10 +160,129 @@ impl TranscriptionResult {
     pub fn finalised(&self) -> bool {
         self.finalised
     }
+
+    /// Words of the best hypothesis with their timings in seconds from the start of the stream
+    pub fn words(&self) -> &[Word] {
+        &self.words
+    }
+
+    /// Other hypotheses for the same audio, best first. Empty unless the backend was asked for them
+    pub fn alternatives(&self) -> &[Alternative] {
+        &self.alternatives
+    }
+
+    /// Backend specific score of the best hypothesis, given by backends that score whole
+    /// hypotheses rather than words. Comparable with the confidences of its alternatives
+    pub fn score(&self) -> Option<f32> {
+        self.score
+    }
+
+    /// Name of the enrolled voice profile that matched this utterance
+    pub fn speaker(&self) -> Option<&str> {
+        self.speaker.as_deref()
+    }
+
+    /// Language tag of the model that produced this, when it was picked automatically
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// The backend that produced this result
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// The utterance's x-vector, when a speaker model is loaded
+    pub fn speaker_embedding(&self) -> Option<&[f32]> {
+        self.speaker_embedding.as_deref()
+    }
+
+    /// Mean of the per-word confidences, if the backend reported any
+    pub fn confidence(&self) -> Option<f32> {
+        let confidences: Vec<f32> = self.words.iter().filter_map(Word::confidence).collect();
+        if confidences.is_empty() {
+            None
+        } else {
+            Some(confidences.iter().sum::<f32>() / confidences.len() as f32)
+        }
+    }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Word {
+    word: String,
+    start: f32,
+    end: f32,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confidence: Option<f32>,
+}
+
+impl Word {
+    fn new(word: &str, start: f32, end: f32, confidence: Option<f32>) -> Self {
+        Self {
+            word: word.to_string(),
+            start,
+            end,
+            confidence,
+        }
+    }
+
+    pub fn word(&self) -> &str {
+        &self.word
+    }
+
+    pub fn start(&self) -> f32 {
+        self.start
+    }
+
+    pub fn end(&self) -> f32 {
+        self.end
+    }
+
+    /// Between 0 and 1. Not every backend scores individual words
+    pub fn confidence(&self) -> Option<f32> {
+        self.confidence
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alternative {
+    text: String,
+    confidence: f32,
+    words: Vec<Word>,
+}
+
+impl Alternative {
+    fn new(text: &str, confidence: f32, words: Vec<Word>) -> Self {
+        Self {
+            text: text.to_string(),
+            confidence,
+            words,
+        }
+    }
+
+    pub fn transcription(&self) -> &str {
+        &self.text
+    }
+
+    /// Backend specific score; only comparable between alternatives of the same result
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    pub fn words(&self) -> &[Word] {
+        &self.words
+    }
+}
+
+use std::{sync::PoisonError, time::Duration};
+
 pub use crossbeam_channel::Sender;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tokio_tungstenite::tungstenite;
+use vocabulary::Vocabulary;
 
 #[derive(Error, Debug)]
 pub enum TranscriptionError {
@@ -39,6 +292,119 @@ pub enum TranscriptionError {
     LocalModel(String),
     #[error("No valid receivers")]
     SendError(String),
+    /// A thread panicked while holding one of the backend's locks
+    #[error("Speech recognition state is unavailable {0}")]
+    Poisoned(String),
+    #[error("Could not connect to {url}: {source}")]
+    Connection {
+        url: String,
+        #[source]
+        source: Box<tungstenite::Error>,
+    },
+    #[error("{url} did not accept the credentials")]
+    Authentication { url: String },
+    /// The service failed to transcribe, it may say how long to wait before trying again
+    #[error("Remote speech recognition failed {message}")]
+    Remote {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// Settings the backend cannot work with, such as a malformed url
+    #[error("Invalid configuration {0}")]
+    Configuration(String),
+    #[error("Invalid grammar {0}")]
+    Grammar(String),
+    #[error("Unsupported operation {0}")]
+    Unsupported(String),
+    #[error("Speaker identification failed {0}")]
+    Speaker(String),
+    #[error("Could not read audio {0}")]
+    Audio(String),
+    #[error("Transcript history failed {0}")]
+    History(String),
+    /// Every backend is backing off or failed to take the audio
+    #[error("No speech recognition backend took the audio {0}")]
+    Unavailable(String),
+}
+
+/// Whether an operation that failed is worth trying again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retry {
+    /// Could work next time, after the usual backoff
+    Backoff,
+    /// The service asked to be left alone for this long
+    After(Duration),
+    /// Keeps failing until something is reconfigured
+    Never,
+}
+
+impl TranscriptionError {
+    pub fn retry(&self) -> Retry {
+        match self {
+            TranscriptionError::Unknown(_)
+            | TranscriptionError::Poisoned(_)
+            | TranscriptionError::Connection { .. }
+            | TranscriptionError::Remote {
+                retry_after: None, ..
+            }
+            | TranscriptionError::History(_)
+            | TranscriptionError::Unavailable(_) => Retry::Backoff,
+            TranscriptionError::Remote {
+                retry_after: Some(wait),
+                ..
+            } => Retry::After(*wait),
+            TranscriptionError::LocalModel(_)
+            | TranscriptionError::SendError(_)
+            | TranscriptionError::Authentication { .. }
+            | TranscriptionError::Configuration(_)
+            | TranscriptionError::Grammar(_)
+            | TranscriptionError::Unsupported(_)
+            | TranscriptionError::Speaker(_)
+            | TranscriptionError::Audio(_) => Retry::Never,
+        }
+    }
+
+    pub fn is_transient(&self) -> bool {
+        self.retry() != Retry::Never
+    }
+
+    fn remote(message: impl Into<String>) -> Self {
+        TranscriptionError::Remote {
+            message: message.into(),
+            retry_after: None,
+        }
+    }
+
+    /// Sorts out why a WebSocket connection to a service failed
+    fn connection(url: &str, error: tungstenite::Error) -> Self {
+        let url = url.to_owned();
+        let tungstenite::Error::Http(response) = &error else {
+            return TranscriptionError::Connection {
+                url,
+                source: Box::new(error),
+            };
+        };
+        let status = response.status();
+        match status.as_u16() {
+            401 | 403 => TranscriptionError::Authentication { url },
+            408 | 429 | 500..=599 => TranscriptionError::Remote {
+                message: format!("{url} answered {status}"),
+                retry_after: response
+                    .headers()
+                    .get("Retry-After")
+                    .and_then(|f| f.to_str().ok())
+                    .and_then(|f| f.trim().parse().ok())
+                    .map(Duration::from_secs),
+            },
+            _ => TranscriptionError::Configuration(format!("{url} answered {status}")),
+        }
+    }
+}
+
+impl<T> From<PoisonError<T>> for TranscriptionError {
+    fn from(error: PoisonError<T>) -> Self {
+        TranscriptionError::Poisoned(error.to_string())
+    }
 }
 
 type Result<T> = std::result::Result<T, TranscriptionError>;
@@ -0,0 +1,88 @@
+use std::{path::Path, time::Duration};
+
+use audio_utils::resample::Resampler;
+use hound::{SampleFormat, WavReader};
+use tracing::trace;
+
+use crate::{Result, TranscriptionError};
+
+/// Mono 16 bit audio read from a file, at the sample rate it was recorded at
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    samples: Vec<i16>,
+    sample_rate: f32,
+}
+
+impl Recording {
+    pub fn open_wav(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let audio = |f: hound::Error| TranscriptionError::Audio(format!("{}: {f}", path.display()));
+        let mut reader = WavReader::open(path).map_err(audio)?;
+        let spec = reader.spec();
+        trace!(
+            channels = spec.channels,
+            sample_rate = spec.sample_rate,
+            bits = spec.bits_per_sample,
+            "reading wav file"
+        );
+
+        let samples = match spec.sample_format {
+            SampleFormat::Float => {
+                let samples = reader
+                    .samples::<f32>()
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(audio)?;
+                audio_utils::resample_i16_mono(&samples, spec.channels)
+            }
+            // scale whatever the bit depth is to 16 bits first
+            SampleFormat::Int => {
+                let bits = i32::from(spec.bits_per_sample);
+                let samples = reader
+                    .samples::<i32>()
+                    .map(|f| {
+                        f.map(|f| match bits {
+                            16 => f as i16,
+                            bits if bits > 16 => (f >> (bits - 16)) as i16,
+                            bits => (f << (16 - bits)) as i16,
+                        })
+                    })
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(audio)?;
+                audio_utils::resample_i16_mono(&samples, spec.channels)
+            }
+        };
+
+        Ok(Self {
+            samples,
+            sample_rate: spec.sample_rate as f32,
+        })
+    }
+
+    /// The whole recording at another sample rate. Unlike a stream fed in chunks, none of the
+    /// end is left behind in the filter
+    pub fn resample(&self, sample_rate: f32) -> Self {
+        let mut resampler = Resampler::new(self.sample_rate, sample_rate);
+        let mut samples = resampler.process_i16(&self.samples);
+        samples.extend(resampler.flush_i16());
+        let length = (self.samples.len() as f64 * f64::from(resampler.output_rate())
+            / f64::from(resampler.input_rate()))
+        .round() as usize;
+        samples.truncate(length);
+        Self {
+            samples,
+            sample_rate,
+        }
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f32(self.samples.len() as f32 / self.sample_rate)
+    }
+}
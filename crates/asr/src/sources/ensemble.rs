@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Alternative, TranscriptionResult, Word};
+
+/// How the finalised hypotheses of an ensemble are merged into one result
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Combination {
+    /// Picks the transcription with the most confidence behind it across backends
+    #[default]
+    Voting,
+    /// Aligns the hypotheses word by word and votes on every position
+    Rover,
+}
+
+/// Merges one finalised hypothesis per backend, given in fallback order so that ties go to the
+/// primary backend
+pub(crate) fn combine(
+    hypotheses: Vec<TranscriptionResult>,
+    combination: Combination,
+) -> Option<TranscriptionResult> {
+    if hypotheses.len() < 2 {
+        return hypotheses.into_iter().next();
+    }
+    Some(match combination {
+        Combination::Voting => vote(hypotheses),
+        Combination::Rover => rover(hypotheses),
+    })
+}
+
+/// Joins the results a backend finalised during one utterance into a single hypothesis, so
+/// backends that split the utterance differently still line up
+pub(crate) fn join(results: Vec<TranscriptionResult>) -> Option<TranscriptionResult> {
+    let mut results = results.into_iter();
+    let first = results.next()?;
+    Some(results.fold(first, |mut joined, result| {
+        joined.text = format!("{} {}", joined.text.trim(), result.text.trim());
+        joined.words.extend(result.words);
+        // alternatives and scores only ever covered part of the utterance
+        joined.alternatives.clear();
+        joined.score = None;
+        joined
+    }))
+}
+
+// backends that do not report confidences get an equal say, as do all of them when none has
+// any confidence at all
+fn weights(hypotheses: &[TranscriptionResult]) -> Vec<f32> {
+    let weights: Vec<f32> = hypotheses
+        .iter()
+        .map(|f| f.confidence().unwrap_or(1.0))
+        .collect();
+    if weights.iter().sum::<f32>() > 0.0 {
+        weights
+    } else {
+        vec![1.0; weights.len()]
+    }
+}
+
+fn normalise(text: &str) -> String {
+    text.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn vote(hypotheses: Vec<TranscriptionResult>) -> TranscriptionResult {
+    let weights = weights(&hypotheses);
+    let total: f32 = weights.iter().sum();
+    // (normalised text, summed weight, first hypothesis with that text)
+    let mut candidates: Vec<(String, f32, TranscriptionResult)> = Vec::new();
+    for (hypothesis, score) in hypotheses.into_iter().zip(weights) {
+        let key = normalise(hypothesis.transcription());
+        match candidates.iter_mut().find(|(text, ..)| *text == key) {
+            Some((_, sum, _)) => *sum += score,
+            None => candidates.push((key, score, hypothesis)),
+        }
+    }
+
+    let mut best = 0;
+    for (i, (_, score, _)) in candidates.iter().enumerate() {
+        if *score > candidates[best].1 {
+            best = i;
+        }
+    }
+    let (_, score, winner) = candidates.remove(best);
+    let alternatives = candidates
+        .into_iter()
+        .map(|(_, score, f)| Alternative::new(&f.text, score / total, f.words))
+        .collect();
+    TranscriptionResult {
+        alternatives,
+        score: Some(score / total),
+        ..winner
+    }
+}
+
+// one entry per hypothesis aligned so far, `None` where that hypothesis has no word
+type Slot = Vec<Option<Word>>;
+
+fn rover(hypotheses: Vec<TranscriptionResult>) -> TranscriptionResult {
+    let weights = weights(&hypotheses);
+    let mut network: Vec<Slot> = Vec::new();
+    for (aligned, hypothesis) in hypotheses.iter().enumerate() {
+        let words = hypothesis_words(hypothesis);
+        network = align(network, words, aligned);
+    }
+
+    let total: f32 = weights.iter().sum();
+    let mut words = Vec::new();
+    for slot in network {
+        // (normalised word, summed weight, best scoring instance)
+        let mut candidates: Vec<(Option<String>, f32, Option<Word>)> = Vec::new();
+        for (entry, hypothesis_weight) in slot.into_iter().zip(&weights) {
+            let key = entry.as_ref().map(|f| f.word.to_lowercase());
+            let score = entry
+                .as_ref()
+                .and_then(Word::confidence)
+                .unwrap_or(*hypothesis_weight);
+            match candidates.iter_mut().find(|(word, ..)| *word == key) {
+                Some((_, sum, _)) => *sum += score,
+                None => candidates.push((key, score, entry)),
+            }
+        }
+        let mut winner = candidates.swap_remove(0);
+        for candidate in candidates {
+            if candidate.1 > winner.1 {
+                winner = candidate;
+            }
+        }
+        if let (_, score, Some(word)) = winner {
+            words.push(Word {
+                confidence: Some(score / total),
+                ..word
+            });
+        }
+    }
+
+    let text = words.iter().map(Word::word).collect::<Vec<_>>().join(" ");
+    let key = normalise(&text);
+    let alternatives = hypotheses
+        .iter()
+        .zip(&weights)
+        .filter(|(f, _)| normalise(f.transcription()) != key)
+        .map(|(f, w)| Alternative::new(f.transcription(), w / total, f.words.clone()))
+        .collect();
+    let first = hypotheses
+        .into_iter()
+        .next()
+        .expect("ensembles combine at least two hypotheses");
+    // the words can come from any backend
+    TranscriptionResult {
+        text,
+        words,
+        alternatives,
+        score: None,
+        ..first
+    }
+    .with_source("ensemble")
+}
+
+// backends without word timings still take part, with their words spread over no time
+fn hypothesis_words(hypothesis: &TranscriptionResult) -> Vec<Word> {
+    if !hypothesis.words().is_empty() {
+        return hypothesis.words().to_vec();
+    }
+    hypothesis
+        .transcription()
+        .split_whitespace()
+        .map(|f| Word::new(f, 0.0, 0.0, None))
+        .collect()
+}
+
+/// Adds a hypothesis to the network with a minimum edit distance alignment. `aligned` is the
+/// number of hypotheses already in every slot
+fn align(network: Vec<Slot>, words: Vec<Word>, aligned: usize) -> Vec<Slot> {
+    let (slots, count) = (network.len(), words.len());
+    // a gap costs 1, a substitution less so similar words line up rather than being split
+    let substitution = |slot: &Slot, word: &Word| {
+        slot.iter()
+            .flatten()
+            .map(|f| word_distance(&f.word, &word.word))
+            .fold(1.0, f32::min)
+    };
+
+    let mut cost = vec![vec![0.0f32; count + 1]; slots + 1];
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i as f32;
+    }
+    cost[0] = (0..=count).map(|f| f as f32).collect();
+    for i in 1..=slots {
+        for j in 1..=count {
+            cost[i][j] = (cost[i - 1][j - 1] + substitution(&network[i - 1], &words[j - 1]))
+                .min(cost[i - 1][j] + 1.0)
+                .min(cost[i][j - 1] + 1.0);
+        }
+    }
+
+    // walk back from the end, then reverse
+    let same = |a: f32, b: f32| (a - b).abs() < f32::EPSILON;
+    let mut network: Vec<Option<Slot>> = network.into_iter().map(Some).collect();
+    let mut words: Vec<Option<Word>> = words.into_iter().map(Some).collect();
+    let mut merged = Vec::with_capacity(slots.max(count));
+    let (mut i, mut j) = (slots, count);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 {
+            let substitution = substitution(
+                network[i - 1].as_ref().expect("each slot is taken once"),
+                words[j - 1].as_ref().expect("each word is taken once"),
+            );
+            if same(cost[i][j], cost[i - 1][j - 1] + substitution) {
+                let mut slot = network[i - 1].take().expect("each slot is taken once");
+                slot.push(words[j - 1].take());
+                merged.push(slot);
+                i -= 1;
+                j -= 1;
+                continue;
+            }
+        }
+        if i > 0 && same(cost[i][j], cost[i - 1][j] + 1.0) {
+            let mut slot = network[i - 1].take().expect("each slot is taken once");
+            slot.push(None);
+            merged.push(slot);
+            i -= 1;
+        } else {
+            let mut slot: Slot = vec![None; aligned];
+            slot.push(words[j - 1].take());
+            merged.push(slot);
+            j -= 1;
+        }
+    }
+    merged.reverse();
+    merged
+}
+
+// 0 for the same word, otherwise between 0.5 and 1 by how many letters differ
+fn word_distance(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    if a == b {
+        return 0.0;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, y) in b.iter().enumerate() {
+            current[j + 1] = (previous[j] + usize::from(x != y))
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        previous = current;
+    }
+    0.5 + 0.5 * previous[b.len()] as f32 / a.len().max(b.len()) as f32
+}
@@ -0,0 +1,172 @@
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info, warn};
+
+use crate::{Retry, TranscriptionError};
+
+/// When a backend is taken out of rotation and how long it stays out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FallbackPolicy {
+    /// Consecutive failures before the circuit opens
+    pub failure_threshold: u32,
+    /// Wait before the first retry of an open circuit
+    pub initial_backoff: Duration,
+    /// The wait doubles after every failed retry up to this. Backends that fail in a way
+    /// retrying cannot fix, such as rejected credentials, wait this long straight away
+    pub max_backoff: Duration,
+}
+
+impl Default for FallbackPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendState {
+    Healthy,
+    /// Has failed recently but is still being tried
+    Degraded,
+    /// Skipped until its retry time comes up
+    Unavailable,
+    /// Being tried again after a backoff; one more failure reopens the circuit
+    Recovering,
+}
+
+impl std::fmt::Display for BackendState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                BackendState::Healthy => "healthy",
+                BackendState::Degraded => "degraded",
+                BackendState::Unavailable => "unavailable",
+                BackendState::Recovering => "recovering",
+            }
+        )
+    }
+}
+
+/// A snapshot of one backend's health, in fallback order
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackendStatus {
+    pub source: String,
+    pub state: BackendState,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    /// Time left before an unavailable backend is tried again
+    pub retry_in: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Health {
+    state: BackendState,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+}
+
+impl Health {
+    pub(crate) fn new(policy: &FallbackPolicy) -> Self {
+        Self {
+            state: BackendState::Healthy,
+            consecutive_failures: 0,
+            last_error: None,
+            backoff: policy.initial_backoff,
+            retry_at: None,
+        }
+    }
+
+    pub(crate) fn state(&self) -> BackendState {
+        self.state
+    }
+
+    /// Whether the backend should get audio now. Moves an open circuit to recovering once
+    /// its backoff has passed
+    pub(crate) fn available(&mut self, source: &str, now: Instant) -> bool {
+        match (self.state, self.retry_at) {
+            (BackendState::Unavailable, Some(retry_at)) if now < retry_at => false,
+            (BackendState::Unavailable, _) => {
+                debug!(source = source, "retrying speech recognition backend");
+                self.state = BackendState::Recovering;
+                true
+            }
+            _ => true,
+        }
+    }
+
+    pub(crate) fn succeeded(&mut self, source: &str, policy: &FallbackPolicy) {
+        if self.state != BackendState::Healthy {
+            info!(source = source, "speech recognition backend recovered");
+        }
+        self.state = BackendState::Healthy;
+        self.consecutive_failures = 0;
+        self.backoff = policy.initial_backoff;
+        self.retry_at = None;
+    }
+
+    pub(crate) fn failed(
+        &mut self,
+        source: &str,
+        error: &TranscriptionError,
+        policy: &FallbackPolicy,
+        now: Instant,
+    ) {
+        self.consecutive_failures += 1;
+        self.last_error = Some(error.to_string());
+        debug!(
+            source = source,
+            failures = self.consecutive_failures,
+            "{error}, trying fallback"
+        );
+
+        // permanent failures and services asking for a pause skip the failure threshold
+        let wait = match (error.retry(), self.state) {
+            (Retry::Never, _) => Some(policy.max_backoff),
+            (Retry::After(wait), _) => Some(wait.max(self.backoff)),
+            (Retry::Backoff, BackendState::Recovering) => {
+                self.backoff = (self.backoff * 2).min(policy.max_backoff);
+                Some(self.backoff)
+            }
+            (Retry::Backoff, _) if self.consecutive_failures >= policy.failure_threshold => {
+                Some(self.backoff)
+            }
+            (Retry::Backoff, _) => None,
+        };
+        match wait {
+            Some(wait) => {
+                if self.state != BackendState::Recovering {
+                    warn!(
+                        source = source,
+                        retry_in = ?wait,
+                        "{error}, taking speech recognition backend out of rotation"
+                    );
+                }
+                self.state = BackendState::Unavailable;
+                self.retry_at = Some(now + wait);
+            }
+            None => self.state = BackendState::Degraded,
+        }
+    }
+
+    pub(crate) fn status(&self, source: &str, now: Instant) -> BackendStatus {
+        BackendStatus {
+            source: source.to_owned(),
+            state: self.state,
+            consecutive_failures: self.consecutive_failures,
+            last_error: self.last_error.clone(),
+            retry_in: match self.state {
+                BackendState::Unavailable => {
+                    self.retry_at.map(|f| f.saturating_duration_since(now))
+                }
+                _ => None,
+            },
+        }
+    }
+}
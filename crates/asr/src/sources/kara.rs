@@ -1,11 +1,279 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+};
 
-use crate::{Result, Transcibe, TranscriptionError, TranscriptionResult};
+use crate::{
+    speaker::SpeakerProfiles, vocabulary::Vocabulary, Alternative, RecognitionMode, Result,
+    Transcibe, TranscriptionError, TranscriptionResult, Word,
+};
 use crossbeam_channel::Sender;
-use tracing::{error, trace};
+use tracing::{debug, error, trace, warn};
 
+/// Settings for a [`LocalRecogniser`] beyond the model itself
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LocalOptions {
+    /// Above zero, finalised results carry that many hypotheses at the cost of per-word
+    /// confidences and speaker embeddings
+    pub max_alternatives: u16,
+    /// A vosk speaker model. Enables x-vectors and matching against enrolled voice profiles
+    pub speaker_model: Option<PathBuf>,
+}
+
+// models stay loaded for as long as something uses them
+static MODELS: ModelCache<vosk::Model> = ModelCache::new();
+static SPEAKER_MODELS: ModelCache<vosk::SpeakerModel> = ModelCache::new();
+
+// the model loaded from one path, locked while it loads
+type Slot<T> = Arc<Mutex<Weak<T>>>;
+
+/// Models by the path they were loaded from, so each is only in memory once
+pub(crate) struct ModelCache<T> {
+    slots: Mutex<Vec<(PathBuf, Slot<T>)>>,
+}
+
+impl<T> ModelCache<T> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            slots: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The model already loaded from `path`, or the result of `load`. Two callers asking for the
+    /// same model do not both load it, one loading a model holds up no one asking for another
+    pub(crate) fn get(&self, path: &Path, load: impl FnOnce(&Path) -> Option<T>) -> Result<Arc<T>> {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        let slot = {
+            let mut slots = self.slots.lock()?;
+            // a slot someone else holds may be mid load
+            slots.retain(|(_, f)| {
+                Arc::strong_count(f) > 1 || f.try_lock().map_or(true, |f| f.strong_count() > 0)
+            });
+            match slots.iter().find(|(f, _)| *f == key) {
+                Some((_, slot)) => Arc::clone(slot),
+                None => {
+                    let slot = Slot::default();
+                    slots.push((key.clone(), Arc::clone(&slot)));
+                    slot
+                }
+            }
+        };
+
+        let mut loaded = slot.lock()?;
+        if let Some(model) = loaded.upgrade() {
+            debug!(path = ?key, "reusing loaded model");
+            return Ok(model);
+        }
+        let model = Arc::new(
+            load(path).ok_or_else(|| TranscriptionError::LocalModel(path.display().to_string()))?,
+        );
+        *loaded = Arc::downgrade(&model);
+        Ok(model)
+    }
+}
+
+// what vosk models are trained on when they do not say
+const DEFAULT_SAMPLE_RATE: f32 = 16_000.0;
+
+/// A vosk model and optional speaker model, shared by every recogniser created from them
+#[derive(Clone)]
+pub struct LocalModel {
+    model: Arc<vosk::Model>,
+    speaker_model: Option<Arc<vosk::SpeakerModel>>,
+    sample_rate: f32,
+}
+
+impl LocalModel {
+    /// Reuses models that are already loaded from the same paths
+    pub fn load(model_path: impl AsRef<Path>, speaker_model: Option<&Path>) -> Result<Self> {
+        let _gag = gag::Gag::stderr().map_err(|_| {
+            TranscriptionError::Unknown(String::from("could not hijack scoped stderr output"))
+        })?;
+        trace!("creating local model");
+        let model = MODELS.get(model_path.as_ref(), |f| {
+            vosk::Model::new(f.to_string_lossy())
+        })?;
+        let speaker_model = match speaker_model {
+            Some(path) => {
+                trace!("creating speaker model");
+                Some(SPEAKER_MODELS.get(path, |f| vosk::SpeakerModel::new(f.to_string_lossy()))?)
+            }
+            None => None,
+        };
+        Ok(Self {
+            model,
+            speaker_model,
+            sample_rate: model_sample_rate(model_path.as_ref()),
+        })
+    }
+
+    /// The rate the model was trained on, which its recognisers take audio at
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+}
+
+// read from the feature settings the model ships with
+fn model_sample_rate(model_path: &Path) -> f32 {
+    let conf = model_path.join("conf").join("mfcc.conf");
+    let sample_rate = std::fs::read_to_string(&conf).ok().and_then(|f| {
+        f.lines()
+            .find_map(|f| f.trim().strip_prefix("--sample-frequency="))
+            .and_then(|f| f.trim().parse().ok())
+    });
+    if sample_rate.is_none() {
+        debug!(path = ?conf, "no sample rate in the model, assuming {DEFAULT_SAMPLE_RATE}");
+    }
+    sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE)
+}
+
+// recognisers kept around per pool, more than this many idle ones are dropped
+pub(crate) const MAX_IDLE: usize = 4;
+
+// the grammar a recogniser was built with, none for dictation
+pub(crate) type Grammar = Option<Vec<String>>;
+
+/// Recognisers given back to a pool, by the grammar they were built with
+pub(crate) struct Idle<T> {
+    recognisers: Mutex<Vec<(Grammar, T)>>,
+}
+
+impl<T> Idle<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            recognisers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.recognisers.lock().map(|f| f.len()).unwrap_or_default()
+    }
+
+    /// One built with `grammar`, if there is one
+    pub(crate) fn take(&self, grammar: &Grammar) -> Result<Option<T>> {
+        let mut recognisers = self.recognisers.lock()?;
+        Ok(recognisers
+            .iter()
+            .position(|(f, _)| f == grammar)
+            .map(|f| recognisers.swap_remove(f).1))
+    }
+
+    /// Kept for the next taker unless there are enough idle already
+    pub(crate) fn give_back(&self, grammar: Grammar, recogniser: T) {
+        if let Ok(mut recognisers) = self.recognisers.lock() {
+            if recognisers.len() < MAX_IDLE {
+                recognisers.push((grammar, recogniser));
+            }
+        }
+    }
+}
+
+/// Recognisers of one model and sample rate. Each stream or session takes its own, and gives it
+/// back when it is done so the next one does not have to build it again
+pub struct RecogniserPool {
+    model: LocalModel,
+    sample_rate: f32,
+    max_alternatives: u16,
+    idle: Idle<vosk::Recognizer>,
+}
+
+impl RecogniserPool {
+    pub fn new(model: LocalModel, sample_rate: f32, max_alternatives: u16) -> Arc<Self> {
+        Arc::new(Self {
+            model,
+            sample_rate,
+            max_alternatives,
+            idle: Idle::new(),
+        })
+    }
+
+    pub fn model(&self) -> &LocalModel {
+        &self.model
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Recognisers ready to be handed out
+    pub fn idle(&self) -> usize {
+        self.idle.len()
+    }
+
+    fn take(
+        self: &Arc<Self>,
+        mode: &RecognitionMode,
+        vocabulary: &Vocabulary,
+    ) -> Result<PooledRecogniser> {
+        let grammar = grammar(mode, vocabulary);
+        let recogniser = match self.idle.take(&grammar)? {
+            Some(recogniser) => recogniser,
+            None => {
+                trace!("creating local recogniser");
+                let _gag = gag::Gag::stderr();
+                build_recogniser(
+                    &self.model.model,
+                    self.model.speaker_model.as_deref(),
+                    self.sample_rate,
+                    self.max_alternatives,
+                    grammar.as_deref(),
+                )?
+            }
+        };
+        Ok(PooledRecogniser {
+            pool: Arc::clone(self),
+            grammar,
+            recogniser: Some(recogniser),
+        })
+    }
+
+    fn give_back(&self, grammar: Grammar, mut recogniser: vosk::Recognizer) {
+        recogniser.reset();
+        self.idle.give_back(grammar, recogniser);
+    }
+}
+
+// returns to its pool when dropped
+struct PooledRecogniser {
+    pool: Arc<RecogniserPool>,
+    grammar: Grammar,
+    recogniser: Option<vosk::Recognizer>,
+}
+
+impl Deref for PooledRecogniser {
+    type Target = vosk::Recognizer;
+
+    fn deref(&self) -> &Self::Target {
+        self.recogniser
+            .as_ref()
+            .expect("only taken out when dropped")
+    }
+}
+
+impl DerefMut for PooledRecogniser {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.recogniser
+            .as_mut()
+            .expect("only taken out when dropped")
+    }
+}
+
+impl Drop for PooledRecogniser {
+    fn drop(&mut self) {
+        if let Some(recogniser) = self.recogniser.take() {
+            self.pool.give_back(self.grammar.take(), recogniser);
+        }
+    }
+}
+
+/// One stream transcribed with a local vosk model
 pub struct LocalRecogniser {
-    recogniser: Arc<Mutex<vosk::Recognizer>>,
+    pool: Arc<RecogniserPool>,
+    speakers: Arc<Mutex<SpeakerProfiles>>,
+    mode: Mutex<RecognitionMode>,
+    vocabulary: Mutex<Vocabulary>,
+    recogniser: Mutex<PooledRecogniser>,
 }
 
 impl Transcibe for LocalRecogniser {
@@ -13,30 +281,30 @@ impl Transcibe for LocalRecogniser {
         "kara"
     }
 
+    fn sample_rate(&self) -> Option<f32> {
+        Some(self.pool.sample_rate)
+    }
+
     fn transcribe(
         &self,
         stream: &[i16],
         result_sender: &Sender<TranscriptionResult>,
     ) -> Result<()> {
-        let recogniser = &mut self
-            .recogniser
-            .lock()
-            .map_err(|f| TranscriptionError::Unknown(f.to_string()))?;
+        let recogniser = &mut self.recogniser.lock()?;
         let state = recogniser.accept_waveform(stream);
         match state {
             vosk::DecodingState::Finalized => {
-                if let Some(result) = recogniser.result().single() {
+                if let Some(result) = self.complete_result(recogniser.result()) {
                     result_sender
-                        .send(TranscriptionResult::new(result.text, true))
+                        .send(result)
                         .map_err(|f| TranscriptionError::SendError(f.to_string()))?;
                 }
             }
             vosk::DecodingState::Running => {
+                let partial = TranscriptionResult::new(recogniser.partial_result().partial, false)
+                    .with_source(self.source());
                 result_sender
-                    .send(TranscriptionResult::new(
-                        recogniser.partial_result().partial,
-                        false,
-                    ))
+                    .send(self.vocabulary()?.replace(partial))
                     .map_err(|f| TranscriptionError::SendError(f.to_string()))?;
             }
             vosk::DecodingState::Failed => {
@@ -45,28 +313,253 @@ impl Transcibe for LocalRecogniser {
         }
         Ok(())
     }
+
+    fn begin_utterance(&self) -> Result<()> {
+        self.reset()
+    }
+
+    fn end_utterance(&self, result_sender: &Sender<TranscriptionResult>) -> Result<()> {
+        let recogniser = &mut self.recogniser.lock()?;
+        // also resets the recogniser for the next utterance
+        if let Some(result) = self.complete_result(recogniser.final_result()) {
+            if !result.transcription().is_empty() {
+                result_sender
+                    .send(result)
+                    .map_err(|f| TranscriptionError::SendError(f.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn reset(&self) -> Result<()> {
+        self.recogniser.lock()?.reset();
+        Ok(())
+    }
+
+    fn set_mode(&self, mode: &RecognitionMode) -> Result<()> {
+        let mut current = self.mode.lock()?;
+        if *current == *mode {
+            return Ok(());
+        }
+        let vocabulary = self.vocabulary()?.clone();
+        *self.recogniser.lock()? = self.pool.take(mode, &vocabulary)?;
+        *current = mode.clone();
+        debug!(source = "kara", mode = ?mode, "recognition mode changed");
+        Ok(())
+    }
+
+    fn set_vocabulary(&self, vocabulary: &Vocabulary) -> Result<()> {
+        let mode = self.mode();
+        match mode {
+            // the phrases join the grammar, which is the only runtime hint vosk takes
+            RecognitionMode::Command(_) => {
+                *self.recogniser.lock()? = self.pool.take(&mode, vocabulary)?;
+            }
+            RecognitionMode::Dictation => {
+                for phrase in vocabulary.phrase_list() {
+                    if let Some(word) = phrase
+                        .split_whitespace()
+                        .find(|f| self.pool.model.model.find_word(f).is_none())
+                    {
+                        warn!(
+                            source = "kara",
+                            phrase = phrase,
+                            "{word} is not in the model, add a replacement for what is heard instead"
+                        );
+                    }
+                }
+                if !vocabulary.phrases.is_empty() && self.pool.max_alternatives == 0 {
+                    debug!(
+                        source = "kara",
+                        "phrases are only boosted when alternatives are enabled"
+                    );
+                }
+            }
+        }
+        *self.vocabulary.lock()? = vocabulary.clone();
+        Ok(())
+    }
 }
 
 impl LocalRecogniser {
-    pub fn new(model_path: impl AsRef<std::path::Path>, sample_rate: f32) -> Result<Self> {
+    /// Loads the model, or shares it if it is already loaded, with a pool of its own. Audio is
+    /// taken at the rate the model was trained on
+    pub fn new(model_path: impl AsRef<Path>, options: &LocalOptions) -> Result<Self> {
         trace!("using local speech recogniser");
-        use gag::Gag;
-        let _gag = Gag::stderr().map_err(|_| {
-            TranscriptionError::Unknown(String::from("could not hijack scoped stderr output"))
-        })?;
-        let model_path = model_path.as_ref().to_string_lossy();
+        let model = LocalModel::load(model_path, options.speaker_model.as_deref())?;
+        if model.speaker_model.is_some() && options.max_alternatives > 0 {
+            warn!(
+                source = "kara",
+                "speaker embeddings are not available with alternatives enabled"
+            );
+        }
+        // profiles are only matched against the embeddings of a speaker model
+        let speakers = match options.speaker_model {
+            Some(_) => {
+                SpeakerProfiles::load(res_def::speaker_profiles_path()).unwrap_or_else(|e| {
+                    error!(source = "kara", "{e}");
+                    SpeakerProfiles::new(res_def::speaker_profiles_path())
+                })
+            }
+            None => SpeakerProfiles::new(res_def::speaker_profiles_path()),
+        };
+        let sample_rate = model.sample_rate();
+        Self::with_pool(
+            RecogniserPool::new(model, sample_rate, options.max_alternatives),
+            Arc::new(Mutex::new(speakers)),
+        )
+    }
 
-        trace!("creating local model");
-        let model = vosk::Model::new(&*model_path)
-            .ok_or_else(|| TranscriptionError::LocalModel(model_path.to_string()))?;
+    fn with_pool(pool: Arc<RecogniserPool>, speakers: Arc<Mutex<SpeakerProfiles>>) -> Result<Self> {
+        let recogniser = pool.take(&RecognitionMode::Dictation, &Vocabulary::default())?;
+        Ok(Self {
+            pool,
+            speakers,
+            mode: Mutex::new(RecognitionMode::Dictation),
+            vocabulary: Mutex::new(Vocabulary::default()),
+            recogniser: Mutex::new(recogniser),
+        })
+    }
 
-        trace!("creating local recogniser");
-        let recogniser = vosk::Recognizer::new(&model, sample_rate).ok_or_else(|| {
-            TranscriptionError::Unknown(String::from("Could not create recogniser from model"))
+    /// Another stream on the same model and voice profiles, starting out in dictation mode
+    pub fn session(&self) -> Result<Self> {
+        Self::with_pool(Arc::clone(&self.pool), Arc::clone(&self.speakers))
+    }
+
+    pub fn pool(&self) -> &Arc<RecogniserPool> {
+        &self.pool
+    }
+
+    pub fn mode(&self) -> RecognitionMode {
+        self.mode.lock().map(|f| f.clone()).unwrap_or_default()
+    }
+
+    /// Adds the speaker embedding of a finalised result to the named voice profile
+    pub fn enrol(&self, name: &str, result: &TranscriptionResult) -> Result<()> {
+        let embedding = result.speaker_embedding().ok_or_else(|| {
+            TranscriptionError::Speaker(String::from("result has no speaker embedding"))
         })?;
+        self.speakers.lock()?.enrol(name, embedding)
+    }
+
+    /// Deletes a voice profile, returning whether it existed
+    pub fn forget_speaker(&self, name: &str) -> Result<bool> {
+        self.speakers.lock()?.remove(name)
+    }
 
-        let recogniser = Arc::new(Mutex::new(recogniser));
+    pub fn speakers(&self) -> Vec<String> {
+        self.speakers
+            .lock()
+            .map(|f| f.profiles().iter().map(|f| f.name().to_owned()).collect())
+            .unwrap_or_default()
+    }
 
-        Ok(Self { recogniser })
+    fn vocabulary(&self) -> Result<std::sync::MutexGuard<'_, Vocabulary>> {
+        self.vocabulary.lock().map_err(TranscriptionError::from)
+    }
+
+    fn complete_result(&self, result: vosk::CompleteResult) -> Option<TranscriptionResult> {
+        let vocabulary = self.vocabulary().ok()?;
+        let result = match result {
+            vosk::CompleteResult::Single(result) => {
+                let words = result
+                    .result
+                    .iter()
+                    .map(|f| Word::new(f.word, f.start, f.end, Some(f.conf)))
+                    .collect();
+                let transcription = TranscriptionResult::new(result.text, true).with_words(words);
+                match result.spk {
+                    Some(embedding) => {
+                        let speaker = self.speakers.lock().ok().and_then(|speakers| {
+                            speakers.identify(&embedding).map(|(profile, similarity)| {
+                                trace!(speaker = profile.name(), similarity, "speaker identified");
+                                profile.name().to_owned()
+                            })
+                        });
+                        transcription.with_speaker(embedding, speaker)
+                    }
+                    None => transcription,
+                }
+            }
+            // vosk does not score individual words when it returns alternatives
+            vosk::CompleteResult::Multiple(result) => {
+                let mut alternatives: Vec<_> = result
+                    .alternatives
+                    .iter()
+                    .map(|alternative| {
+                        let words = alternative
+                            .result
+                            .iter()
+                            .map(|f| Word::new(f.word, f.start, f.end, None))
+                            .collect();
+                        Alternative::new(alternative.text, alternative.confidence, words)
+                    })
+                    .collect();
+                if alternatives.is_empty() {
+                    return None;
+                }
+                vocabulary.rescore(&mut alternatives);
+                let best = alternatives.remove(0);
+                TranscriptionResult::new(&best.text, true)
+                    .with_score(best.confidence)
+                    .with_words(best.words)
+                    .with_alternatives(alternatives)
+            }
+        };
+        Some(vocabulary.replace(result.with_source(self.source())))
+    }
+}
+
+// dictation has no grammar, commands always allow speech outside it
+pub(crate) fn grammar(mode: &RecognitionMode, vocabulary: &Vocabulary) -> Grammar {
+    let RecognitionMode::Command(phrases) = mode else {
+        return None;
+    };
+    let mut grammar: Vec<String> = phrases
+        .iter()
+        .map(|f| f.to_lowercase())
+        .chain(vocabulary.phrase_list())
+        .collect();
+    // lets speech outside the grammar come out as unknown instead of a forced match
+    if !grammar.iter().any(|f| f == "[unk]") {
+        grammar.push(String::from("[unk]"));
+    }
+    Some(grammar)
+}
+
+// reuses the loaded model, only the decoding graph changes
+fn build_recogniser(
+    model: &vosk::Model,
+    speaker_model: Option<&vosk::SpeakerModel>,
+    sample_rate: f32,
+    max_alternatives: u16,
+    grammar: Option<&[String]>,
+) -> Result<vosk::Recognizer> {
+    let recogniser = match grammar {
+        None => vosk::Recognizer::new(model, sample_rate),
+        Some(grammar) => {
+            for phrase in grammar.iter().filter(|f| *f != "[unk]") {
+                if let Some(word) = phrase
+                    .split_whitespace()
+                    .find(|f| model.find_word(f).is_none())
+                {
+                    warn!(
+                        source = "kara",
+                        phrase = phrase,
+                        "{word} is not in the model"
+                    );
+                }
+            }
+            vosk::Recognizer::new_with_grammar(model, sample_rate, grammar)
+        }
+    };
+    let mut recogniser = recogniser.ok_or_else(|| {
+        TranscriptionError::Unknown(String::from("Could not create recogniser from model"))
+    })?;
+    recogniser.set_words(true);
+    recogniser.set_max_alternatives(max_alternatives);
+    if let Some(speaker_model) = speaker_model {
+        recogniser.set_speaker_model(speaker_model);
     }
+    Ok(recogniser)
 }
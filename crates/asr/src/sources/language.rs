@@ -0,0 +1,287 @@
+use std::sync::{Mutex, MutexGuard};
+
+use audio_utils::resample::Resampler;
+use crossbeam_channel::{Receiver, Sender};
+use tracing::{debug, trace};
+
+use crate::{
+    vocabulary::Vocabulary, RecognitionMode, Result, Transcibe, TranscriptionError,
+    TranscriptionResult,
+};
+
+// how much of a segment every candidate hears before one is picked
+const PROBE_SECONDS: f32 = 1.5;
+
+/// Decides which language each segment of speech is in by scoring the first moments of it with
+/// every candidate model, then leaves the rest of the segment to the most confident one
+pub struct LanguageIdentifier {
+    candidates: Vec<Candidate>,
+    sample_rate: f32,
+    probe_samples: usize,
+    state: Mutex<State>,
+}
+
+struct Candidate {
+    language: String,
+    source: Mutex<Box<dyn Transcibe>>,
+    // for models that take audio at another rate than the stream
+    resampler: Mutex<Option<Resampler>>,
+}
+
+#[derive(Default)]
+struct State {
+    // audio of the current segment while the language is still undecided
+    probe: Vec<i16>,
+    winner: Option<usize>,
+}
+
+struct Score {
+    confidence: f32,
+    results: Vec<TranscriptionResult>,
+}
+
+impl LanguageIdentifier {
+    /// Takes each candidate with its language tag, usually one local model per language. Audio
+    /// is fed at `sample_rate` and resampled for candidates that need another rate
+    pub fn new(candidates: Vec<(String, Box<dyn Transcibe>)>, sample_rate: f32) -> Result<Self> {
+        if candidates.is_empty() {
+            return Err(TranscriptionError::LocalModel(String::from(
+                "no models to identify languages with",
+            )));
+        }
+        Ok(Self {
+            candidates: candidates
+                .into_iter()
+                .map(|(language, source)| Candidate {
+                    language,
+                    resampler: Mutex::new(
+                        source
+                            .sample_rate()
+                            .filter(|f| *f != sample_rate)
+                            .map(|f| Resampler::new(sample_rate, f)),
+                    ),
+                    source: Mutex::new(source),
+                })
+                .collect(),
+            sample_rate,
+            probe_samples: (sample_rate * PROBE_SECONDS) as usize,
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.candidates.iter().map(|f| f.language.as_str())
+    }
+
+    fn state(&self) -> Result<MutexGuard<'_, State>> {
+        self.state.lock().map_err(TranscriptionError::from)
+    }
+
+    // runs the probe through every candidate at once and returns the best one with what it heard
+    fn identify(&self, probe: &[i16]) -> Result<(usize, Vec<TranscriptionResult>)> {
+        let scores: Vec<Result<Score>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .candidates
+                .iter()
+                .map(|candidate| scope.spawn(move || candidate.score(probe)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|f| {
+                    f.join().unwrap_or_else(|_| {
+                        Err(TranscriptionError::Unknown(String::from(
+                            "language identification panicked",
+                        )))
+                    })
+                })
+                .collect()
+        });
+
+        let scores = scores.into_iter().collect::<Result<Vec<_>>>()?;
+        for (candidate, score) in self.candidates.iter().zip(&scores) {
+            trace!(
+                language = candidate.language,
+                confidence = score.confidence,
+                "language score"
+            );
+        }
+        // ties go to the candidate listed first
+        let best = scores.into_iter().enumerate().reduce(|best, f| {
+            if f.1.confidence > best.1.confidence {
+                f
+            } else {
+                best
+            }
+        });
+        let (winner, score) = best.expect("there is at least one candidate");
+        debug!(
+            language = self.candidates[winner].language,
+            "language identified"
+        );
+        Ok((winner, score.results))
+    }
+
+    fn forward(
+        &self,
+        winner: usize,
+        results: &Receiver<TranscriptionResult>,
+        result_sender: &Sender<TranscriptionResult>,
+    ) -> Result<bool> {
+        let mut finalised = false;
+        for result in results.try_iter() {
+            finalised |= result.finalised();
+            result_sender
+                .send(result.with_language(&self.candidates[winner].language))
+                .map_err(|f| TranscriptionError::SendError(f.to_string()))?;
+        }
+        Ok(finalised)
+    }
+}
+
+impl Candidate {
+    fn source(&self) -> Result<MutexGuard<'_, Box<dyn Transcibe>>> {
+        self.source.lock().map_err(TranscriptionError::from)
+    }
+
+    fn transcribe(
+        &self,
+        stream: &[i16],
+        result_sender: &Sender<TranscriptionResult>,
+    ) -> Result<()> {
+        match self.resampler.lock()?.as_mut() {
+            Some(resampler) => self
+                .source()?
+                .transcribe(&resampler.process_i16(stream), result_sender),
+            None => self.source()?.transcribe(stream, result_sender),
+        }
+    }
+
+    fn reset(&self) -> Result<()> {
+        if let Some(resampler) = self.resampler.lock()?.as_mut() {
+            resampler.reset();
+        }
+        self.source()?.reset()
+    }
+
+    // how sure the model is of what it made of the probe, it is left ready for a fresh segment
+    fn score(&self, probe: &[i16]) -> Result<Score> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.reset()?;
+        self.source()?.begin_utterance()?;
+        self.transcribe(probe, &tx)?;
+        self.source()?.end_utterance(&tx)?;
+        self.reset()?;
+
+        let results: Vec<_> = rx.try_iter().filter(|f| f.finalised()).collect();
+        // models asked for alternatives only score whole hypotheses
+        let confidences: Vec<f32> = results
+            .iter()
+            .filter_map(|f| f.confidence().or(f.score()))
+            .collect();
+        let confidence = if confidences.is_empty() {
+            0.0
+        } else {
+            confidences.iter().sum::<f32>() / confidences.len() as f32
+        };
+        Ok(Score {
+            confidence,
+            results,
+        })
+    }
+}
+
+impl Transcibe for LanguageIdentifier {
+    fn source(&self) -> &str {
+        "kara"
+    }
+
+    fn sample_rate(&self) -> Option<f32> {
+        Some(self.sample_rate)
+    }
+
+    fn transcribe(
+        &self,
+        stream: &[i16],
+        result_sender: &Sender<TranscriptionResult>,
+    ) -> Result<()> {
+        let mut state = self.state()?;
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let winner = match state.winner {
+            Some(winner) => {
+                self.candidates[winner].transcribe(stream, &tx)?;
+                winner
+            }
+            None => {
+                state.probe.extend_from_slice(stream);
+                if state.probe.len() < self.probe_samples {
+                    return Ok(());
+                }
+                // the winner hears the probe again so its results cover the whole segment
+                let probe = std::mem::take(&mut state.probe);
+                let (winner, _) = self.identify(&probe)?;
+                self.candidates[winner].transcribe(&probe, &tx)?;
+                state.winner = Some(winner);
+                winner
+            }
+        };
+        // the next segment may be in another language
+        if self.forward(winner, &rx, result_sender)? {
+            state.winner = None;
+        }
+        Ok(())
+    }
+
+    fn begin_utterance(&self) -> Result<()> {
+        *self.state()? = State::default();
+        for candidate in &self.candidates {
+            candidate.reset()?;
+            candidate.source()?.begin_utterance()?;
+        }
+        Ok(())
+    }
+
+    fn end_utterance(&self, result_sender: &Sender<TranscriptionResult>) -> Result<()> {
+        let mut state = self.state()?;
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let winner = match state.winner {
+            Some(winner) => {
+                self.candidates[winner].source()?.end_utterance(&tx)?;
+                winner
+            }
+            None if state.probe.is_empty() => return Ok(()),
+            // too short to probe, what the winner made of it while scoring is all there is
+            None => {
+                let (winner, results) = self.identify(&state.probe)?;
+                for result in results {
+                    let _ = tx.send(result);
+                }
+                winner
+            }
+        };
+        *state = State::default();
+        self.forward(winner, &rx, result_sender)?;
+        Ok(())
+    }
+
+    fn reset(&self) -> Result<()> {
+        *self.state()? = State::default();
+        for candidate in &self.candidates {
+            candidate.reset()?;
+        }
+        Ok(())
+    }
+
+    fn set_mode(&self, mode: &RecognitionMode) -> Result<()> {
+        for candidate in &self.candidates {
+            candidate.source()?.set_mode(mode)?;
+        }
+        Ok(())
+    }
+
+    fn set_vocabulary(&self, vocabulary: &Vocabulary) -> Result<()> {
+        for candidate in &self.candidates {
+            candidate.source()?.set_vocabulary(vocabulary)?;
+        }
+        Ok(())
+    }
+}
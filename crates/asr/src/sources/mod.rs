@@ -1,20 +1,44 @@
+pub(crate) mod ensemble;
+mod health;
 pub mod kara;
+pub mod language;
+pub mod vosk_server;
+pub mod watson;
+mod worker;
 
-use std::{collections::VecDeque, path::PathBuf};
+pub use ensemble::Combination;
+pub use health::{BackendState, BackendStatus, FallbackPolicy};
 
-use crossbeam_channel::Sender;
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Instant,
+};
+
+use audio_utils::resample::Resampler;
+use crossbeam_channel::{Receiver, Sender};
 
 use res_def::{model_path, vosk_model_url};
 use serde::{Deserialize, Serialize};
-use tracing::{error, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
-use crate::{Transcibe, TranscriptionError, TranscriptionResult};
+use self::{health::Health, worker::Worker};
+use crate::{
+    recording::Recording, vocabulary::Vocabulary, RecognitionMode, Transcibe, TranscriptionError,
+    TranscriptionResult,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 #[serde(tag = "source")]
 pub enum Source {
     Kara {
+        /// Catalogue id, used instead of `model-path` and `fallback-url` when set
+        #[serde(skip_serializing_if = "Option::is_none")]
+        model: Option<String>,
+
         #[serde(rename = "model-path")]
         #[serde(default = "model_path")]
         model_path: PathBuf,
@@ -22,6 +46,14 @@ pub enum Source {
         #[serde(rename = "fallback-url")]
         #[serde(default = "vosk_link")]
         fallback_url: String,
+
+        #[serde(rename = "max-alternatives")]
+        #[serde(default)]
+        max_alternatives: u16,
+
+        #[serde(rename = "speaker-model-path")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        speaker_model_path: Option<PathBuf>,
     },
 
     #[serde(rename = "ibm-watson")]
@@ -34,6 +66,16 @@ pub enum Source {
         #[serde(default = "empty_string")]
         service_url: String,
     },
+
+    #[serde(rename = "vosk-server")]
+    VoskServer {
+        #[serde(default = "empty_string")]
+        url: String,
+
+        #[serde(rename = "max-alternatives")]
+        #[serde(default)]
+        max_alternatives: u16,
+    },
 }
 
 fn vosk_link() -> String {
@@ -47,8 +89,11 @@ fn empty_string() -> String {
 impl Default for Source {
     fn default() -> Self {
         Self::Kara {
+            model: None,
             model_path: PathBuf::new(),
             fallback_url: vosk_link(),
+            max_alternatives: 0,
+            speaker_model_path: None,
         }
     }
 }
@@ -58,6 +103,7 @@ impl ToString for Source {
         match &self {
             Source::Kara { .. } => "kara",
             Source::IBMWatson { .. } => "ibm-watson",
+            Source::VoskServer { .. } => "vosk-server",
         }
         .to_owned()
     }
@@ -65,7 +111,77 @@ impl ToString for Source {
 
 #[derive(Default)]
 pub struct SpeechRecognisers {
-    sources: VecDeque<Box<dyn Transcibe>>,
+    sources: VecDeque<Backend>,
+    mode: RecognitionMode,
+    policy: FallbackPolicy,
+    ensemble: Option<Combination>,
+    vocabulary: Vocabulary,
+    // rate of the audio being fed, backends wanting another one get it resampled
+    input_rate: Option<f32>,
+}
+
+// finalised results a backend may hold before the ensemble combines them without waiting for the
+// utterance to end
+const MAX_PENDING_FINALS: usize = 3;
+
+struct Backend {
+    name: String,
+    sample_rate: Option<f32>,
+    source: Arc<Mutex<Box<dyn Transcibe>>>,
+    // transcribes in ensemble mode
+    worker: Option<Worker>,
+    // has been fed audio since the utterance began
+    in_utterance: bool,
+    health: Health,
+    // in ensemble mode results are collected here and combined before being sent on
+    results: Sender<TranscriptionResult>,
+    pending: Receiver<TranscriptionResult>,
+    finals: VecDeque<TranscriptionResult>,
+    resampler: Option<Resampler>,
+}
+
+impl Backend {
+    fn new(source: Box<dyn Transcibe>, policy: &FallbackPolicy, input_rate: Option<f32>) -> Self {
+        let (results, pending) = crossbeam_channel::unbounded();
+        let name = source.source().to_owned();
+        let sample_rate = source.sample_rate();
+        let resampler = resampler(&name, sample_rate, input_rate);
+        Self {
+            name,
+            sample_rate,
+            source: Arc::new(Mutex::new(source)),
+            worker: None,
+            in_utterance: false,
+            health: Health::new(policy),
+            results,
+            pending,
+            finals: VecDeque::new(),
+            resampler,
+        }
+    }
+
+    fn source(&self) -> Result<MutexGuard<'_, Box<dyn Transcibe>>, TranscriptionError> {
+        self.source.lock().map_err(TranscriptionError::from)
+    }
+
+    fn start_worker(&mut self) {
+        if self.worker.is_none() {
+            trace!(source = self.name, "starting transcription worker");
+            self.worker = Some(Worker::new(Arc::clone(&self.source), self.results.clone()));
+        }
+    }
+
+    // the feed at the rate the backend takes it
+    fn resample<'a>(&mut self, feed: &'a [i16]) -> Cow<'a, [i16]> {
+        match &mut self.resampler {
+            Some(resampler) => Cow::Owned(resampler.process_i16(feed)),
+            None => Cow::Borrowed(feed),
+        }
+    }
+
+    fn available(&self) -> bool {
+        self.in_utterance && self.health.state() != BackendState::Unavailable
+    }
 }
 
 impl SpeechRecognisers {
@@ -77,33 +193,417 @@ impl SpeechRecognisers {
     pub fn add(&mut self, source: Box<dyn Transcibe>) {
         let source_name = source.source();
         trace!(source = source_name, "adding speech recognition backend");
-        self.sources.push_back(source);
+        let backend = self.backend(source);
+        self.sources.push_back(backend);
     }
 
     pub fn add_primary(&mut self, source: Box<dyn Transcibe>) {
         let source_name = source.source().to_string();
         trace!(source = source_name, "setting primary backend");
-        self.sources.push_front(source);
+        let backend = self.backend(source);
+        self.sources.push_front(backend);
         info!(source = source_name, "using primary backend");
     }
 
+    /// Swaps in a backend for the first one with the same source name, keeping its place in the
+    /// fallback order, or makes it the primary backend if there is none. The current utterance
+    /// is finalised first so the audio stream carries on into the new backend
+    pub fn replace(
+        &mut self,
+        source: Box<dyn Transcibe>,
+        result_sender: &Sender<TranscriptionResult>,
+    ) {
+        self.end_utterance(result_sender);
+        let source_name = source.source().to_string();
+        match self.sources.iter().position(|f| f.name == source_name) {
+            Some(position) => {
+                // the old backend's worker stops as it is dropped
+                self.sources[position] = self.backend(source);
+                info!(source = source_name, "replaced backend");
+            }
+            None => self.add_primary(source),
+        }
+    }
+
+    // a backend set up with the current mode and vocabulary, and a worker in ensemble mode
+    fn backend(&self, source: Box<dyn Transcibe>) -> Backend {
+        self.apply_mode(source.as_ref());
+        self.apply_vocabulary(source.as_ref());
+        let mut backend = Backend::new(source, &self.policy, self.input_rate);
+        if self.ensemble.is_some() {
+            backend.start_worker();
+        }
+        backend
+    }
+
+    /// The rate of the audio that will be fed in, each backend gets it at the rate it asks for
+    pub fn set_input_rate(&mut self, sample_rate: f32) {
+        if self.input_rate == Some(sample_rate) {
+            return;
+        }
+        self.input_rate = Some(sample_rate);
+        for backend in self.sources.iter_mut() {
+            backend.resampler = resampler(&backend.name, backend.sample_rate, self.input_rate);
+        }
+    }
+
+    /// Applies to failures from now on, backends keep their current state
+    pub fn set_policy(&mut self, policy: FallbackPolicy) {
+        self.policy = policy;
+    }
+
+    /// Health of every backend in fallback order
+    pub fn status(&self) -> Vec<BackendStatus> {
+        let now = Instant::now();
+        self.sources
+            .iter()
+            .map(|f| f.health.status(&f.name, now))
+            .collect()
+    }
+
+    /// Runs every backend on the same audio, each on a thread of its own, and combines what they
+    /// finalised once the utterance ends. `None` goes back to primary and fallback ordering
+    pub fn set_ensemble(
+        &mut self,
+        combination: Option<Combination>,
+        result_sender: &Sender<TranscriptionResult>,
+    ) {
+        if self.ensemble == combination {
+            return;
+        }
+        self.end_utterance(result_sender);
+        self.ensemble = combination;
+        for backend in self.sources.iter_mut() {
+            match combination {
+                Some(_) => backend.start_worker(),
+                None => backend.worker = None,
+            }
+        }
+    }
+
+    pub fn mode(&self) -> &RecognitionMode {
+        &self.mode
+    }
+
+    /// Applies the mode to every backend that supports it. Backends added later pick it up too.
+    /// The current utterance is finalised first so its last words are not lost
+    pub fn set_mode(&mut self, mode: RecognitionMode, result_sender: &Sender<TranscriptionResult>) {
+        if self.mode == mode {
+            return;
+        }
+        self.end_utterance(result_sender);
+        self.mode = mode;
+        for backend in self.sources.iter() {
+            match backend.source() {
+                Ok(source) => self.apply_mode(source.as_ref()),
+                Err(e) => warn!(source = backend.name, "{e}"),
+            }
+        }
+    }
+
+    fn apply_mode(&self, source: &dyn Transcibe) {
+        if let Err(e) = source.set_mode(&self.mode) {
+            match e {
+                TranscriptionError::Unsupported(_) if self.mode == RecognitionMode::Dictation => {}
+                e => warn!(source = source.source(), "{e}"),
+            }
+        }
+    }
+
+    /// Hands the vocabulary to every backend, including ones added later. The current utterance
+    /// is finalised first as remote backends start a new session to take it
+    pub fn set_vocabulary(
+        &mut self,
+        vocabulary: &Vocabulary,
+        result_sender: &Sender<TranscriptionResult>,
+    ) {
+        if self.vocabulary == *vocabulary {
+            return;
+        }
+        self.end_utterance(result_sender);
+        self.vocabulary = vocabulary.clone();
+        for backend in self.sources.iter() {
+            match backend.source() {
+                Ok(source) => self.apply_vocabulary(source.as_ref()),
+                Err(e) => warn!(source = backend.name, "{e}"),
+            }
+        }
+    }
+
+    fn apply_vocabulary(&self, source: &dyn Transcibe) {
+        if let Err(e) = source.set_vocabulary(&self.vocabulary) {
+            match e {
+                TranscriptionError::Unsupported(_) if self.vocabulary.is_empty() => {}
+                e => warn!(source = source.source(), "{e}"),
+            }
+        }
+    }
+
     pub fn valid(&self) -> bool {
         !self.sources.is_empty()
     }
 
+    pub fn begin_utterance(&mut self) {
+        for backend in self.sources.iter_mut() {
+            if let Err(e) = backend.source().and_then(|f| f.begin_utterance()) {
+                error!(source = backend.name, "{e}");
+            }
+            backend.in_utterance = false;
+        }
+    }
+
+    /// Flushes every backend that heard part of the utterance
+    pub fn end_utterance(&mut self, result_sender: &Sender<TranscriptionResult>) {
+        let now = Instant::now();
+        for backend in self.sources.iter_mut().filter(|f| f.in_utterance) {
+            trace!(source = backend.name, "ending utterance");
+            let sender = match self.ensemble {
+                Some(_) => &backend.results,
+                None => result_sender,
+            };
+            if let Err(e) = backend.source().and_then(|f| f.end_utterance(sender)) {
+                error!(source = backend.name, "{e}");
+                backend.health.failed(&backend.name, &e, &self.policy, now);
+            }
+        }
+
+        if let Some(combination) = self.ensemble {
+            if let Err(e) = self.gather(result_sender) {
+                error!("{e}");
+            }
+            if let Err(e) = self.combine_finals(combination, result_sender) {
+                error!("{e}");
+            }
+        }
+        for backend in self.sources.iter_mut() {
+            backend.in_utterance = false;
+        }
+    }
+
+    /// Feeds a whole recording through as one utterance and returns its finalised results
+    pub fn transcribe_recording(
+        &mut self,
+        recording: &Recording,
+    ) -> Result<Vec<TranscriptionResult>, TranscriptionError> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        // converted up front when every backend takes the same rate, the rest get it resampled
+        // as it is fed
+        let converted;
+        let rates: Vec<f32> = self.sources.iter().filter_map(|f| f.sample_rate).collect();
+        let recording = match rates.first() {
+            Some(&rate) if rate != recording.sample_rate() && rates.iter().all(|f| *f == rate) => {
+                debug!(
+                    from = recording.sample_rate(),
+                    to = rate,
+                    "resampling recording"
+                );
+                converted = recording.resample(rate);
+                &converted
+            }
+            _ => recording,
+        };
+        // about as much as a microphone hands over at a time
+        let chunk = (recording.sample_rate() / 10.0).max(1.0) as usize;
+
+        self.set_input_rate(recording.sample_rate());
+        self.begin_utterance();
+        let mut results = Vec::new();
+        for samples in recording.samples().chunks(chunk) {
+            self.speech_to_text(samples, &tx)?;
+            results.extend(rx.try_iter().filter(TranscriptionResult::finalised));
+        }
+        self.end_utterance(&tx);
+        results.extend(rx.try_iter().filter(TranscriptionResult::finalised));
+        Ok(results)
+    }
+
+    pub fn reset(&mut self) {
+        for backend in self.sources.iter_mut() {
+            if let Err(e) = backend.source().and_then(|f| f.reset()) {
+                error!(source = backend.name, "{e}");
+            }
+            backend.in_utterance = false;
+            if let Some(resampler) = &mut backend.resampler {
+                resampler.reset();
+            }
+            backend.finals.clear();
+            backend.pending.try_iter().for_each(drop);
+        }
+    }
+
+    /// Feeds the first backend that is not backing off after repeated failures. A failing
+    /// backend is skipped until its retry time and takes its place again once it recovers. It is
+    /// an error when no backend took the audio
     pub fn speech_to_text(
-        &self,
+        &mut self,
+        feed: &[i16],
+        result_sender: &Sender<TranscriptionResult>,
+    ) -> Result<(), TranscriptionError> {
+        if let Some(combination) = self.ensemble {
+            return self.ensemble_to_text(feed, result_sender, combination);
+        }
+        let now = Instant::now();
+        let mut reasons = Vec::new();
+        for i in self.sources.iter_mut() {
+            if !i.health.available(&i.name, now) {
+                reasons.push(format!("{} is backing off", i.name));
+                continue;
+            }
+            let feed = i.resample(feed);
+            match i.source().and_then(|f| f.transcribe(&feed, result_sender)) {
+                Ok(()) => {
+                    i.health.succeeded(&i.name, &self.policy);
+                    i.in_utterance = true;
+                    return Ok(());
+                }
+                Err(e) => {
+                    reasons.push(format!("{}: {e}", i.name));
+                    i.health.failed(&i.name, &e, &self.policy, now);
+                }
+            }
+        }
+        Err(unheard(reasons))
+    }
+
+    /// Feeds every available backend at once. Partial results come from the first of them,
+    /// finalised ones are held back until the utterance ends so each backend's take on it can be
+    /// combined
+    fn ensemble_to_text(
+        &mut self,
         feed: &[i16],
         result_sender: &Sender<TranscriptionResult>,
+        combination: Combination,
+    ) -> Result<(), TranscriptionError> {
+        let now = Instant::now();
+        let mut reasons = Vec::new();
+        let mut started = Vec::new();
+        for (i, backend) in self.sources.iter_mut().enumerate() {
+            if !backend.health.available(&backend.name, now) {
+                reasons.push(format!("{} is backing off", backend.name));
+                continue;
+            }
+            let chunk = backend.resample(feed).into_owned();
+            backend.start_worker();
+            let sent = match &backend.worker {
+                Some(worker) => worker.transcribe(chunk),
+                None => continue,
+            };
+            match sent {
+                Ok(()) => started.push(i),
+                Err(e) => {
+                    reasons.push(format!("{}: {e}", backend.name));
+                    backend.health.failed(&backend.name, &e, &self.policy, now);
+                }
+            }
+        }
+
+        let mut heard = false;
+        for i in started {
+            let backend = &mut self.sources[i];
+            let Some(worker) = &backend.worker else {
+                continue;
+            };
+            match worker.wait() {
+                Ok(()) => {
+                    backend.health.succeeded(&backend.name, &self.policy);
+                    backend.in_utterance = true;
+                    heard = true;
+                }
+                Err(e) => {
+                    reasons.push(format!("{}: {e}", backend.name));
+                    backend.health.failed(&backend.name, &e, &self.policy, now);
+                }
+            }
+        }
+
+        self.gather(result_sender)?;
+        for backend in self.sources.iter_mut().filter(|f| !f.available()) {
+            // dropped out of the utterance, what it heard so far would not line up
+            backend.finals.clear();
+        }
+        // without an end to the utterance in sight, do not hold on to speech indefinitely
+        if self
+            .sources
+            .iter()
+            .any(|f| f.finals.len() > MAX_PENDING_FINALS)
+        {
+            self.combine_finals(combination, result_sender)?;
+        }
+        if heard {
+            Ok(())
+        } else {
+            Err(unheard(reasons))
+        }
+    }
+
+    // combines everything the backends finalised, each backend's results joined into one
+    fn combine_finals(
+        &mut self,
+        combination: Combination,
+        result_sender: &Sender<TranscriptionResult>,
+    ) -> Result<(), TranscriptionError> {
+        let hypotheses = self
+            .sources
+            .iter_mut()
+            .filter_map(|f| ensemble::join(f.finals.drain(..).collect()))
+            .collect();
+        send_combined(hypotheses, combination, result_sender)
+    }
+
+    // forwards partial results from the leading backend and queues finalised ones
+    fn gather(
+        &mut self,
+        result_sender: &Sender<TranscriptionResult>,
     ) -> Result<(), TranscriptionError> {
-        for i in self.sources.iter() {
-            if let Err(e) = i.transcribe(feed, result_sender) {
-                error!(source = i.source(), "{}, trying fallback", e.to_string());
-            } else {
-                // trace!("transcription completed");
-                break;
+        let leader = self.sources.iter().position(Backend::available);
+        for (i, backend) in self.sources.iter_mut().enumerate() {
+            for result in backend.pending.try_iter() {
+                if result.finalised() {
+                    // silence is finalised by some backends and not others
+                    if !result.transcription().is_empty() {
+                        backend.finals.push_back(result);
+                    }
+                } else if Some(i) == leader {
+                    result_sender
+                        .send(result)
+                        .map_err(|f| TranscriptionError::SendError(f.to_string()))?;
+                }
             }
         }
         Ok(())
     }
 }
+
+fn resampler(source: &str, sample_rate: Option<f32>, input_rate: Option<f32>) -> Option<Resampler> {
+    let (input_rate, sample_rate) = (input_rate?, sample_rate?);
+    if input_rate == sample_rate {
+        return None;
+    }
+    debug!(
+        source,
+        input_rate, sample_rate, "resampling audio for backend"
+    );
+    Some(Resampler::new(input_rate, sample_rate))
+}
+
+// why a chunk of audio went unheard, one reason per backend
+fn unheard(reasons: Vec<String>) -> TranscriptionError {
+    if reasons.is_empty() {
+        TranscriptionError::Unavailable(String::from("no backends"))
+    } else {
+        TranscriptionError::Unavailable(reasons.join(", "))
+    }
+}
+
+fn send_combined(
+    hypotheses: Vec<TranscriptionResult>,
+    combination: Combination,
+    result_sender: &Sender<TranscriptionResult>,
+) -> Result<(), TranscriptionError> {
+    match ensemble::combine(hypotheses, combination) {
+        Some(result) => result_sender
+            .send(result)
+            .map_err(|f| TranscriptionError::SendError(f.to_string())),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,363 @@
+use std::{sync::Mutex, time::Duration};
+
+use crossbeam_channel::{Receiver, Sender};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, error, trace};
+use url::Url;
+
+use crate::{
+    sources::kara::grammar, vocabulary::Vocabulary, Alternative, RecognitionMode, Result,
+    Transcibe, TranscriptionError, TranscriptionResult, Word,
+};
+
+/// Streams audio to a vosk-server, or anything speaking its WebSocket protocol, so recognition
+/// can run on another machine
+pub struct VoskServerRecogniser {
+    url: Url,
+    sample_rate: f32,
+    max_alternatives: u16,
+    mode: Mutex<RecognitionMode>,
+    vocabulary: Mutex<Vocabulary>,
+    session: Mutex<Option<Session>>,
+}
+
+// how long to wait for the server's final result after the end of an utterance
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+// vosk-server matches this text exactly in some versions
+const END_OF_STREAM: &str = r#"{"eof" : 1}"#;
+
+struct Session {
+    commands: UnboundedSender<Command>,
+    events: Receiver<SessionEvent>,
+}
+
+enum Command {
+    Audio(Vec<i16>),
+    Stop,
+}
+
+enum SessionEvent {
+    Transcription(TranscriptionResult),
+    // the server has answered everything up to the end of stream
+    Flushed,
+    Failed(TranscriptionError),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ServerMessage {
+    Partial {
+        partial: String,
+    },
+    Alternatives {
+        alternatives: Vec<ServerAlternative>,
+    },
+    Final {
+        text: String,
+        #[serde(default)]
+        result: Vec<ServerWord>,
+    },
+}
+
+#[derive(Deserialize)]
+struct ServerAlternative {
+    confidence: f32,
+    #[serde(default)]
+    result: Vec<ServerWord>,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct ServerWord {
+    conf: Option<f32>,
+    start: f32,
+    end: f32,
+    word: String,
+}
+
+impl ServerWord {
+    fn into_word(self) -> Word {
+        Word::new(&self.word, self.start, self.end, self.conf)
+    }
+}
+
+impl ServerMessage {
+    fn into_transcription(self, vocabulary: &Vocabulary) -> Option<TranscriptionResult> {
+        let result = match self {
+            ServerMessage::Partial { partial } => TranscriptionResult::new(&partial, false),
+            ServerMessage::Final { text, result } => TranscriptionResult::new(&text, true)
+                .with_words(result.into_iter().map(ServerWord::into_word).collect()),
+            ServerMessage::Alternatives { alternatives } => {
+                let mut alternatives: Vec<_> = alternatives
+                    .into_iter()
+                    .map(|f| {
+                        let words = f.result.into_iter().map(ServerWord::into_word).collect();
+                        Alternative::new(&f.text, f.confidence, words)
+                    })
+                    .collect();
+                if alternatives.is_empty() {
+                    return None;
+                }
+                vocabulary.rescore(&mut alternatives);
+                let best = alternatives.remove(0);
+                TranscriptionResult::new(&best.text, true)
+                    .with_score(best.confidence)
+                    .with_words(best.words)
+                    .with_alternatives(alternatives)
+            }
+        };
+        Some(vocabulary.replace(result.with_source("vosk-server")))
+    }
+}
+
+impl Transcibe for VoskServerRecogniser {
+    fn source(&self) -> &str {
+        "vosk-server"
+    }
+
+    fn sample_rate(&self) -> Option<f32> {
+        Some(self.sample_rate)
+    }
+
+    fn transcribe(
+        &self,
+        stream: &[i16],
+        result_sender: &Sender<TranscriptionResult>,
+    ) -> Result<()> {
+        let mut session = self.session.lock()?;
+        if session.is_none() {
+            *session = Some(self.start_session()?);
+        }
+        let Some(active) = session.as_ref() else {
+            return Err(session_ended());
+        };
+
+        if active
+            .commands
+            .send(Command::Audio(stream.to_vec()))
+            .is_err()
+        {
+            *session = None;
+            return Err(session_ended());
+        }
+
+        let events: Vec<_> = active.events.try_iter().collect();
+        for event in events {
+            match event {
+                SessionEvent::Transcription(result) => result_sender
+                    .send(result)
+                    .map_err(|f| TranscriptionError::SendError(f.to_string()))?,
+                SessionEvent::Flushed => {}
+                SessionEvent::Failed(e) => {
+                    *session = None;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_mode(&self, mode: &RecognitionMode) -> Result<()> {
+        *self.mode.lock()? = mode.clone();
+        // the phrase list is only read when a stream is configured
+        self.reset()
+    }
+
+    fn set_vocabulary(&self, vocabulary: &Vocabulary) -> Result<()> {
+        *self.vocabulary.lock()? = vocabulary.clone();
+        self.reset()
+    }
+
+    fn end_utterance(&self, result_sender: &Sender<TranscriptionResult>) -> Result<()> {
+        let mut session = self.session.lock()?;
+        // the server closes the stream after its final result, the next utterance gets a new one
+        let Some(active) = session.take() else {
+            return Ok(());
+        };
+        if active.commands.send(Command::Stop).is_err() {
+            return Err(session_ended());
+        }
+
+        loop {
+            match active.events.recv_timeout(FLUSH_TIMEOUT) {
+                Ok(SessionEvent::Transcription(result)) => result_sender
+                    .send(result)
+                    .map_err(|f| TranscriptionError::SendError(f.to_string()))?,
+                Ok(SessionEvent::Flushed) => return Ok(()),
+                Ok(SessionEvent::Failed(e)) => return Err(e),
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
+                    return Err(TranscriptionError::remote(
+                        "timed out waiting for vosk-server's final result",
+                    ))
+                }
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
+                    return Err(session_ended())
+                }
+            }
+        }
+    }
+
+    fn reset(&self) -> Result<()> {
+        *self.session.lock()? = None;
+        Ok(())
+    }
+}
+
+fn session_ended() -> TranscriptionError {
+    TranscriptionError::remote("vosk-server session is no longer running")
+}
+
+impl VoskServerRecogniser {
+    pub fn new(url: impl AsRef<str>, sample_rate: f32, max_alternatives: u16) -> Result<Self> {
+        trace!("using vosk-server speech recogniser");
+        let url = Url::parse(url.as_ref())
+            .map_err(|f| TranscriptionError::Configuration(format!("{}: {f}", url.as_ref())))?;
+        if !matches!(url.scheme(), "ws" | "wss") {
+            return Err(TranscriptionError::Configuration(format!(
+                "unsupported url scheme {}",
+                url.scheme()
+            )));
+        }
+        debug!(url = url.as_str(), "vosk-server endpoint");
+        Ok(Self {
+            url,
+            sample_rate,
+            max_alternatives,
+            mode: Mutex::new(RecognitionMode::default()),
+            vocabulary: Mutex::new(Vocabulary::default()),
+            session: Mutex::new(None),
+        })
+    }
+
+    fn start_session(&self) -> Result<Session> {
+        let mode = self.mode.lock()?;
+        let vocabulary = self.vocabulary.lock()?.clone();
+        let mut config = serde_json::json!({
+            "sample_rate": self.sample_rate,
+            "words": 1,
+            "max_alternatives": self.max_alternatives,
+        });
+        // custom phrases can only be hinted to a grammar, the same one a local model is given
+        if let Some(phrases) = grammar(&mode, &vocabulary) {
+            config["phrase_list"] = serde_json::json!(phrases);
+        }
+        let config = serde_json::json!({ "config": config }).to_string();
+        Ok(Session::start(self.url.clone(), config, vocabulary))
+    }
+}
+
+impl Session {
+    fn start(url: Url, config: String, vocabulary: Vocabulary) -> Self {
+        let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+        let (events_tx, events_rx) = crossbeam_channel::unbounded();
+
+        std::thread::spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    let _ = events_tx.send(SessionEvent::Failed(TranscriptionError::Unknown(
+                        e.to_string(),
+                    )));
+                    return;
+                }
+            };
+            runtime.block_on(async move {
+                if let Err(e) =
+                    stream_audio(url, &config, &vocabulary, commands_rx, &events_tx).await
+                {
+                    error!(source = "vosk-server", "{e}");
+                    let _ = events_tx.send(SessionEvent::Failed(e));
+                }
+            });
+            trace!("vosk-server session ended");
+        });
+
+        Self {
+            commands: commands_tx,
+            events: events_rx,
+        }
+    }
+}
+
+async fn stream_audio(
+    url: Url,
+    config: &str,
+    vocabulary: &Vocabulary,
+    mut commands: UnboundedReceiver<Command>,
+    events: &Sender<SessionEvent>,
+) -> Result<()> {
+    let connection = |f| TranscriptionError::connection(url.as_str(), f);
+
+    trace!("connecting to vosk-server");
+    let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str())
+        .await
+        .map_err(connection)?;
+    socket
+        .send(Message::Text(config.to_owned()))
+        .await
+        .map_err(connection)?;
+
+    let send_error = |f: crossbeam_channel::SendError<SessionEvent>| {
+        TranscriptionError::SendError(f.to_string())
+    };
+
+    // the server answers every chunk and the end of stream with exactly one message
+    let mut awaiting_replies = 0;
+    let mut stopping = false;
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Audio(chunk)) => {
+                    let bytes = chunk.iter().flat_map(|f| f.to_le_bytes()).collect();
+                    socket.send(Message::Binary(bytes)).await.map_err(connection)?;
+                    awaiting_replies += 1;
+                }
+                Some(Command::Stop) => {
+                    socket.send(Message::Text(END_OF_STREAM.to_owned())).await.map_err(connection)?;
+                    awaiting_replies += 1;
+                    stopping = true;
+                }
+                None => {
+                    trace!("audio feed closed, stopping vosk-server session");
+                    let _ = socket.close(None).await;
+                    return Ok(());
+                }
+            },
+            message = socket.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    awaiting_replies -= 1;
+                    let message: ServerMessage = serde_json::from_str(&text)
+                        .map_err(|f| TranscriptionError::remote(f.to_string()))?;
+                    if let Some(result) = message.into_transcription(vocabulary) {
+                        events.send(SessionEvent::Transcription(result)).map_err(send_error)?;
+                    }
+                    if stopping && awaiting_replies <= 0 {
+                        stopping = false;
+                        events.send(SessionEvent::Flushed).map_err(send_error)?;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None if !stopping && awaiting_replies <= 0 => {
+                    return Ok(());
+                }
+                Some(Ok(Message::Close(frame))) => {
+                    return Err(TranscriptionError::remote(format!(
+                        "vosk-server closed the connection {}",
+                        frame.map(|f| f.reason.to_string()).unwrap_or_default()
+                    )));
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(connection(e)),
+                None => {
+                    return Err(TranscriptionError::remote("vosk-server connection ended"))
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use std::{sync::Mutex, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossbeam_channel::{Receiver, Sender};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValue, Message};
+use tracing::{debug, error, trace};
+use url::Url;
+
+use crate::{
+    vocabulary::Vocabulary, Alternative, Result, Transcibe, TranscriptionError,
+    TranscriptionResult, Word,
+};
+
+/// Streams audio to IBM Watson Speech to Text over its WebSocket recognize interface
+pub struct WatsonRecogniser {
+    api_key: String,
+    recognize_url: Url,
+    sample_rate: f32,
+    vocabulary: Mutex<Vocabulary>,
+    session: Mutex<Option<Session>>,
+}
+
+// how long to wait for watson's final results after stopping an utterance
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+// hypotheses asked for when there are phrases to boost
+const RESCORED_ALTERNATIVES: u16 = 5;
+
+struct Session {
+    commands: UnboundedSender<Command>,
+    events: Receiver<SessionEvent>,
+}
+
+enum Command {
+    Audio(Vec<i16>),
+    Stop,
+}
+
+enum SessionEvent {
+    Transcription(TranscriptionResult),
+    // watson has sent everything for the stopped utterance
+    Flushed,
+    Failed(TranscriptionError),
+}
+
+#[derive(Deserialize)]
+struct WatsonMessage {
+    #[serde(default)]
+    results: Vec<WatsonResult>,
+    error: Option<String>,
+    state: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WatsonResult {
+    #[serde(rename = "final")]
+    finalised: bool,
+    alternatives: Vec<WatsonAlternative>,
+}
+
+#[derive(Deserialize)]
+struct WatsonAlternative {
+    transcript: String,
+    #[serde(default)]
+    confidence: f32,
+    // [word, start, end]
+    #[serde(default)]
+    timestamps: Vec<(String, f32, f32)>,
+    // [word, confidence]
+    #[serde(default)]
+    word_confidence: Vec<(String, f32)>,
+}
+
+impl WatsonAlternative {
+    fn words(&self) -> Vec<Word> {
+        self.timestamps
+            .iter()
+            .enumerate()
+            .map(|(i, (word, start, end))| {
+                let confidence = self.word_confidence.get(i).map(|(_, f)| *f);
+                Word::new(word, *start, *end, confidence)
+            })
+            .collect()
+    }
+}
+
+impl WatsonResult {
+    fn into_transcription(self, vocabulary: &Vocabulary) -> Option<TranscriptionResult> {
+        let mut alternatives: Vec<_> = self
+            .alternatives
+            .into_iter()
+            .map(|f| Alternative::new(f.transcript.trim(), f.confidence, f.words()))
+            .collect();
+        if alternatives.is_empty() {
+            return None;
+        }
+        vocabulary.rescore(&mut alternatives);
+        let best = alternatives.remove(0);
+        Some(
+            vocabulary.replace(
+                TranscriptionResult::new(&best.text, self.finalised)
+                    .with_words(best.words)
+                    .with_alternatives(alternatives)
+                    .with_source("ibm-watson"),
+            ),
+        )
+    }
+}
+
+impl Transcibe for WatsonRecogniser {
+    fn source(&self) -> &str {
+        "ibm-watson"
+    }
+
+    fn sample_rate(&self) -> Option<f32> {
+        Some(self.sample_rate)
+    }
+
+    fn transcribe(
+        &self,
+        stream: &[i16],
+        result_sender: &Sender<TranscriptionResult>,
+    ) -> Result<()> {
+        let mut session = self.session.lock()?;
+        if session.is_none() {
+            *session = Some(self.start_session()?);
+        }
+        let Some(active) = session.as_ref() else {
+            return Err(session_ended());
+        };
+
+        if active
+            .commands
+            .send(Command::Audio(stream.to_vec()))
+            .is_err()
+        {
+            *session = None;
+            return Err(session_ended());
+        }
+
+        let events: Vec<_> = active.events.try_iter().collect();
+        for event in events {
+            match event {
+                SessionEvent::Transcription(result) => result_sender
+                    .send(result)
+                    .map_err(|f| TranscriptionError::SendError(f.to_string()))?,
+                SessionEvent::Flushed => {}
+                SessionEvent::Failed(e) => {
+                    *session = None;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn begin_utterance(&self) -> Result<()> {
+        let mut session = self.session.lock()?;
+        if session.is_none() {
+            *session = Some(self.start_session()?);
+        }
+        Ok(())
+    }
+
+    fn end_utterance(&self, result_sender: &Sender<TranscriptionResult>) -> Result<()> {
+        let mut session = self.session.lock()?;
+        let Some(active) = session.as_ref() else {
+            return Ok(());
+        };
+        if active.commands.send(Command::Stop).is_err() {
+            *session = None;
+            return Err(session_ended());
+        }
+
+        loop {
+            match active.events.recv_timeout(FLUSH_TIMEOUT) {
+                Ok(SessionEvent::Transcription(result)) => result_sender
+                    .send(result)
+                    .map_err(|f| TranscriptionError::SendError(f.to_string()))?,
+                Ok(SessionEvent::Flushed) => return Ok(()),
+                Ok(SessionEvent::Failed(e)) => {
+                    *session = None;
+                    return Err(e);
+                }
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
+                    *session = None;
+                    return Err(TranscriptionError::remote(
+                        "timed out waiting for watson's final results",
+                    ));
+                }
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
+                    *session = None;
+                    return Err(session_ended());
+                }
+            }
+        }
+    }
+
+    fn set_vocabulary(&self, vocabulary: &Vocabulary) -> Result<()> {
+        *self.vocabulary.lock()? = vocabulary.clone();
+        // the start message of the next session carries it
+        self.reset()
+    }
+
+    fn reset(&self) -> Result<()> {
+        // the worker stops the recognition and closes the socket once its feed is dropped
+        *self.session.lock()? = None;
+        Ok(())
+    }
+}
+
+fn session_ended() -> TranscriptionError {
+    TranscriptionError::remote("watson session is no longer running")
+}
+
+impl WatsonRecogniser {
+    pub fn new(
+        api_key: impl AsRef<str>,
+        service_url: impl AsRef<str>,
+        sample_rate: f32,
+    ) -> Result<Self> {
+        trace!("using ibm watson speech recogniser");
+        let recognize_url = recognize_url(service_url.as_ref())?;
+        debug!(url = recognize_url.as_str(), "watson recognize endpoint");
+        Ok(Self {
+            api_key: api_key.as_ref().to_owned(),
+            recognize_url,
+            sample_rate,
+            vocabulary: Mutex::new(Vocabulary::default()),
+            session: Mutex::new(None),
+        })
+    }
+
+    fn start_session(&self) -> Result<Session> {
+        let vocabulary = self.vocabulary.lock()?.clone();
+        Ok(Session::start(
+            self.recognize_url.clone(),
+            self.api_key.clone(),
+            self.sample_rate,
+            vocabulary,
+        ))
+    }
+}
+
+impl Session {
+    fn start(url: Url, api_key: String, sample_rate: f32, vocabulary: Vocabulary) -> Self {
+        let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+        let (events_tx, events_rx) = crossbeam_channel::unbounded();
+
+        std::thread::spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    let _ = events_tx.send(SessionEvent::Failed(TranscriptionError::Unknown(
+                        e.to_string(),
+                    )));
+                    return;
+                }
+            };
+            runtime.block_on(async move {
+                if let Err(e) = stream_audio(
+                    url,
+                    &api_key,
+                    sample_rate,
+                    &vocabulary,
+                    commands_rx,
+                    &events_tx,
+                )
+                .await
+                {
+                    error!(source = "ibm-watson", "{e}");
+                    let _ = events_tx.send(SessionEvent::Failed(e));
+                }
+            });
+            trace!("watson session ended");
+        });
+
+        Self {
+            commands: commands_tx,
+            events: events_rx,
+        }
+    }
+}
+
+/// Turns a service url as given by IBM Cloud into the WebSocket recognize endpoint
+fn recognize_url(service_url: &str) -> Result<Url> {
+    let mut url = Url::parse(service_url.trim_end_matches('/'))
+        .map_err(|f| TranscriptionError::Configuration(format!("{service_url}: {f}")))?;
+    let scheme = match url.scheme() {
+        "https" | "wss" => "wss",
+        "http" | "ws" => "ws",
+        scheme => {
+            return Err(TranscriptionError::Configuration(format!(
+                "unsupported url scheme {scheme}"
+            )))
+        }
+    };
+    url.set_scheme(scheme).map_err(|_| {
+        TranscriptionError::Configuration(format!("could not use {scheme} for {url}"))
+    })?;
+    if !url.path().ends_with("/v1/recognize") {
+        let path = format!("{}/v1/recognize", url.path().trim_end_matches('/'));
+        url.set_path(&path);
+    }
+    Ok(url)
+}
+
+async fn stream_audio(
+    url: Url,
+    api_key: &str,
+    sample_rate: f32,
+    vocabulary: &Vocabulary,
+    mut commands: UnboundedReceiver<Command>,
+    events: &Sender<SessionEvent>,
+) -> Result<()> {
+    let connection = |f| TranscriptionError::connection(url.as_str(), f);
+
+    let mut request = url
+        .as_str()
+        .into_client_request()
+        .map_err(|f| TranscriptionError::Configuration(f.to_string()))?;
+    let credentials = STANDARD.encode(format!("apikey:{api_key}"));
+    let authorisation = HeaderValue::from_str(&format!("Basic {credentials}"))
+        .map_err(|_| TranscriptionError::Configuration(String::from("invalid api key")))?;
+    request.headers_mut().insert("Authorization", authorisation);
+
+    trace!("connecting to watson");
+    let (mut socket, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .map_err(connection)?;
+
+    let mut start = serde_json::json!({
+        "action": "start",
+        "content-type": format!(
+            "audio/l16;rate={};channels=1;endianness=little-endian",
+            sample_rate as u32
+        ),
+        "interim_results": true,
+        "inactivity_timeout": -1,
+        "timestamps": true,
+        "word_confidence": true,
+    });
+    // watson has no runtime hints without a custom model, its alternatives are rescored instead
+    if !vocabulary.phrases.is_empty() {
+        start["max_alternatives"] = serde_json::json!(RESCORED_ALTERNATIVES);
+    }
+    let stop = serde_json::json!({ "action": "stop" });
+    let send_error = |f: crossbeam_channel::SendError<SessionEvent>| {
+        TranscriptionError::SendError(f.to_string())
+    };
+
+    let mut started = false;
+    let mut stopping = false;
+    // watson acknowledges every start with a listening state too
+    let mut unacknowledged_starts = 0;
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Audio(chunk)) => {
+                    if !started {
+                        socket.send(Message::Text(start.to_string())).await.map_err(connection)?;
+                        started = true;
+                        unacknowledged_starts += 1;
+                    }
+                    let bytes = chunk.iter().flat_map(|f| f.to_le_bytes()).collect();
+                    socket.send(Message::Binary(bytes)).await.map_err(connection)?;
+                }
+                Some(Command::Stop) => {
+                    if started {
+                        socket.send(Message::Text(stop.to_string())).await.map_err(connection)?;
+                        started = false;
+                        stopping = true;
+                    } else {
+                        events.send(SessionEvent::Flushed).map_err(send_error)?;
+                    }
+                }
+                None => {
+                    trace!("audio feed closed, stopping watson session");
+                    if started {
+                        socket.send(Message::Text(stop.to_string())).await.map_err(connection)?;
+                    }
+                    let _ = socket.close(None).await;
+                    return Ok(());
+                }
+            },
+            message = socket.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let message: WatsonMessage = serde_json::from_str(&text)
+                        .map_err(|f| TranscriptionError::remote(f.to_string()))?;
+                    if let Some(error) = message.error {
+                        return Err(TranscriptionError::remote(error));
+                    }
+                    for result in message.results.into_iter().filter_map(|f| f.into_transcription(vocabulary)) {
+                        events.send(SessionEvent::Transcription(result)).map_err(send_error)?;
+                    }
+                    if let Some(state) = message.state {
+                        trace!(state = state, "watson state");
+                        // watson is listening again once the stopped utterance is fully answered
+                        if state == "listening" {
+                            if unacknowledged_starts > 0 {
+                                unacknowledged_starts -= 1;
+                            } else if stopping {
+                                stopping = false;
+                                events.send(SessionEvent::Flushed).map_err(send_error)?;
+                            }
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(frame))) => {
+                    return Err(TranscriptionError::remote(format!(
+                        "watson closed the connection {}",
+                        frame.map(|f| f.reason.to_string()).unwrap_or_default()
+                    )));
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(connection(e)),
+                None => {
+                    return Err(TranscriptionError::remote("watson connection ended"))
+                }
+            },
+        }
+    }
+}
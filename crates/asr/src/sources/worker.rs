@@ -0,0 +1,71 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use tracing::trace;
+
+use crate::{Transcibe, TranscriptionError, TranscriptionResult};
+
+/// Transcribes for one backend on a thread of its own, so an ensemble hears each chunk with all
+/// of its backends at once. The thread lives until the worker is dropped
+pub(crate) struct Worker {
+    chunks: Option<Sender<Vec<i16>>>,
+    done: Receiver<Result<(), TranscriptionError>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    pub(crate) fn new(
+        source: Arc<Mutex<Box<dyn Transcibe>>>,
+        result_sender: Sender<TranscriptionResult>,
+    ) -> Self {
+        // one chunk at a time, it is waited for before the next is handed over
+        let (chunks, feed) = crossbeam_channel::bounded::<Vec<i16>>(1);
+        let (finished, done) = crossbeam_channel::bounded(1);
+        let thread = std::thread::spawn(move || {
+            for chunk in feed {
+                let result = source
+                    .lock()
+                    .map_err(TranscriptionError::from)
+                    .and_then(|f| f.transcribe(&chunk, &result_sender));
+                if finished.send(result).is_err() {
+                    break;
+                }
+            }
+            trace!("transcription worker stopped");
+        });
+        Self {
+            chunks: Some(chunks),
+            done,
+            thread: Some(thread),
+        }
+    }
+
+    /// Starts on a chunk, `wait` says how it went
+    pub(crate) fn transcribe(&self, chunk: Vec<i16>) -> Result<(), TranscriptionError> {
+        self.chunks
+            .as_ref()
+            .and_then(|f| f.send(chunk).ok())
+            .ok_or_else(stopped)
+    }
+
+    pub(crate) fn wait(&self) -> Result<(), TranscriptionError> {
+        self.done.recv().unwrap_or_else(|_| Err(stopped()))
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // the thread finishes once the channel it is fed from closes
+        self.chunks.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn stopped() -> TranscriptionError {
+    TranscriptionError::Unknown(String::from("transcription worker stopped"))
+}
@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, trace, warn};
+
+use crate::{Result, TranscriptionError};
+
+/// Cosine similarity an embedding needs to reach before it is attributed to a profile
+pub const DEFAULT_MATCH_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeakerProfile {
+    name: String,
+    embedding: Vec<f32>,
+    utterances: u32,
+}
+
+impl SpeakerProfile {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Running mean of every x-vector enrolled for this speaker
+    pub fn embedding(&self) -> &[f32] {
+        &self.embedding
+    }
+
+    pub fn utterances(&self) -> u32 {
+        self.utterances
+    }
+}
+
+/// Voice profiles stored as one JSON file per speaker
+#[derive(Debug, Clone)]
+pub struct SpeakerProfiles {
+    directory: PathBuf,
+    profiles: Vec<SpeakerProfile>,
+    threshold: f32,
+}
+
+impl SpeakerProfiles {
+    /// No profiles yet, enrolled ones are saved to `directory`
+    pub fn new(directory: impl AsRef<Path>) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+            profiles: Vec::new(),
+            threshold: DEFAULT_MATCH_THRESHOLD,
+        }
+    }
+
+    /// Reads every profile in `directory`. A missing directory gives an empty set, a profile that
+    /// cannot be read is left out
+    pub fn load(directory: impl AsRef<Path>) -> Result<Self> {
+        let mut speakers = Self::new(directory);
+        let directory = &speakers.directory;
+        if directory.is_dir() {
+            let entries = std::fs::read_dir(directory).map_err(speaker_error(directory))?;
+            for entry in entries {
+                let path = entry.map_err(speaker_error(directory))?.path();
+                if path.extension().map(|f| f == "json").unwrap_or(false) {
+                    match read_profile(&path) {
+                        Ok(profile) => {
+                            trace!(speaker = profile.name, "loaded voice profile");
+                            speakers.profiles.push(profile);
+                        }
+                        Err(e) => warn!("{e}"),
+                    }
+                }
+            }
+        }
+        debug!(count = speakers.profiles.len(), "voice profiles loaded");
+        Ok(speakers)
+    }
+
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    pub fn profiles(&self) -> &[SpeakerProfile] {
+        &self.profiles
+    }
+
+    /// Adds an utterance's embedding to the named profile, creating it if needed, and saves it
+    pub fn enrol(&mut self, name: &str, embedding: &[f32]) -> Result<()> {
+        if name.is_empty() || name.contains(|f: char| std::path::is_separator(f) || f == '.') {
+            return Err(TranscriptionError::Speaker(format!(
+                "{name:?} cannot be used as a speaker name"
+            )));
+        }
+        if embedding.is_empty() {
+            return Err(TranscriptionError::Speaker(String::from(
+                "no speaker embedding to enrol",
+            )));
+        }
+
+        let index = match self.profiles.iter().position(|f| f.name == name) {
+            Some(index) => {
+                let profile = &mut self.profiles[index];
+                if profile.embedding.len() != embedding.len() {
+                    return Err(TranscriptionError::Speaker(format!(
+                        "embedding size {} does not match the profile of {name}",
+                        embedding.len()
+                    )));
+                }
+                let count = profile.utterances as f32;
+                for (current, new) in profile.embedding.iter_mut().zip(embedding) {
+                    *current = (*current * count + new) / (count + 1.0);
+                }
+                profile.utterances += 1;
+                index
+            }
+            None => {
+                self.profiles.push(SpeakerProfile {
+                    name: name.to_owned(),
+                    embedding: embedding.to_vec(),
+                    utterances: 1,
+                });
+                self.profiles.len() - 1
+            }
+        };
+
+        let profile = &self.profiles[index];
+        std::fs::create_dir_all(&self.directory).map_err(speaker_error(&self.directory))?;
+        let path = self.profile_path(name);
+        let contents = serde_json::to_string(profile)
+            .map_err(|f| TranscriptionError::Speaker(f.to_string()))?;
+        std::fs::write(&path, contents).map_err(speaker_error(&path))?;
+        debug!(
+            speaker = name,
+            utterances = profile.utterances,
+            "voice profile saved"
+        );
+        Ok(())
+    }
+
+    /// Deletes a profile, returning whether it existed
+    pub fn remove(&mut self, name: &str) -> Result<bool> {
+        let Some(index) = self.profiles.iter().position(|f| f.name == name) else {
+            return Ok(false);
+        };
+        self.profiles.remove(index);
+        let path = self.profile_path(name);
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(speaker_error(&path))?;
+        }
+        Ok(true)
+    }
+
+    /// The closest profile and its similarity, if it clears the match threshold
+    pub fn identify(&self, embedding: &[f32]) -> Option<(&SpeakerProfile, f32)> {
+        self.profiles
+            .iter()
+            .filter(|f| f.embedding.len() == embedding.len())
+            .map(|f| (f, cosine_similarity(&f.embedding, embedding)))
+            .filter(|(_, similarity)| *similarity >= self.threshold)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    fn profile_path(&self, name: &str) -> PathBuf {
+        let mut path = self.directory.join(name);
+        path.set_extension("json");
+        path
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm_a = a.iter().map(|f| f * f).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|f| f * f).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+fn read_profile(path: &Path) -> Result<SpeakerProfile> {
+    let contents = std::fs::read_to_string(path).map_err(speaker_error(path))?;
+    serde_json::from_str(&contents)
+        .map_err(|f| TranscriptionError::Speaker(format!("{}: {f}", path.display())))
+}
+
+fn speaker_error(path: &Path) -> impl Fn(std::io::Error) -> TranscriptionError + '_ {
+    move |f| TranscriptionError::Speaker(format!("{}: {f}", path.display()))
+}
@@ -0,0 +1,121 @@
+use super::support::Mock;
+use crate::{
+    sources::{ensemble::combine, Combination, SpeechRecognisers},
+    TranscriptionResult, Word,
+};
+
+fn hypothesis(words: &[(&str, f32)]) -> TranscriptionResult {
+    let text = words.iter().map(|(f, _)| *f).collect::<Vec<_>>().join(" ");
+    let words = words
+        .iter()
+        .enumerate()
+        .map(|(i, (word, confidence))| Word::new(word, i as f32, i as f32 + 1.0, Some(*confidence)))
+        .collect();
+    TranscriptionResult::new(&text, true).with_words(words)
+}
+
+#[test]
+fn voting_prefers_the_most_confident_agreement() {
+    let hypotheses = vec![
+        hypothesis(&[("turn", 0.6), ("on", 0.6), ("the", 0.6), ("light", 0.6)]),
+        hypothesis(&[("turn", 0.5), ("on", 0.5), ("the", 0.5), ("lights", 0.5)]),
+        hypothesis(&[("Turn", 0.4), ("on", 0.4), ("the", 0.4), ("lights", 0.4)]),
+    ];
+    let result = combine(hypotheses, Combination::Voting).unwrap();
+    assert_eq!(result.transcription(), "turn on the lights");
+    assert_eq!(result.alternatives().len(), 1);
+    assert_eq!(
+        result.alternatives()[0].transcription(),
+        "turn on the light"
+    );
+}
+
+#[test]
+fn rover_votes_on_every_word() {
+    let hypotheses = vec![
+        hypothesis(&[("turn", 0.9), ("of", 0.4), ("the", 0.9), ("lights", 0.9)]),
+        hypothesis(&[("turn", 0.8), ("off", 0.7), ("lights", 0.6)]),
+        hypothesis(&[("turn", 0.7), ("off", 0.6), ("the", 0.5), ("light", 0.3)]),
+    ];
+    let result = combine(hypotheses, Combination::Rover).unwrap();
+    assert_eq!(result.transcription(), "turn off the lights");
+    assert_eq!(result.words().len(), 4);
+    assert_eq!(result.words()[3].start(), 3.0);
+    assert_eq!(result.alternatives().len(), 3);
+}
+
+#[test]
+fn hypotheses_without_any_confidence_get_equal_weight() {
+    let hypotheses = || {
+        vec![
+            hypothesis(&[("turn", 0.0), ("on", 0.0), ("the", 0.0), ("light", 0.0)]),
+            hypothesis(&[("turn", 0.0), ("on", 0.0), ("the", 0.0), ("lights", 0.0)]),
+            hypothesis(&[("turn", 0.0), ("on", 0.0), ("the", 0.0), ("lights", 0.0)]),
+        ]
+    };
+    let result = combine(hypotheses(), Combination::Voting).unwrap();
+    assert_eq!(result.transcription(), "turn on the lights");
+    assert_eq!(result.score(), Some(2.0 / 3.0));
+    assert_eq!(result.alternatives()[0].confidence(), 1.0 / 3.0);
+
+    let result = combine(hypotheses(), Combination::Rover).unwrap();
+    assert!(result.words().iter().all(|f| f.confidence() == Some(0.0)));
+    assert!(result
+        .alternatives()
+        .iter()
+        .all(|f| !f.confidence().is_nan()));
+}
+
+// partially hears "turn" and finalises `text` for every chunk
+fn scripted(text: &'static str) -> Box<Mock> {
+    Box::new(Mock::new(text).with_replies(vec![
+        TranscriptionResult::new("turn", false),
+        TranscriptionResult::new(text, true),
+    ]))
+}
+
+#[test]
+fn ensemble_sends_one_combined_result() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut recognisers = SpeechRecognisers::new();
+    recognisers.add(scripted("turn on the light"));
+    recognisers.add(scripted("turn on the lights"));
+    recognisers.add(scripted("turn on the lights"));
+    recognisers.set_ensemble(Some(Combination::Voting), &tx);
+
+    recognisers.speech_to_text(&[0; 160], &tx).unwrap();
+    let partials: Vec<_> = rx.try_iter().collect();
+    assert_eq!(partials.len(), 1);
+    assert!(!partials[0].finalised());
+
+    recognisers.end_utterance(&tx);
+    let results: Vec<_> = rx.try_iter().collect();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].transcription(), "turn on the lights");
+}
+
+#[test]
+fn ensemble_combines_whole_utterances() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut recognisers = SpeechRecognisers::new();
+    // finalises the utterance in two parts where the others see one
+    recognisers.add(Box::new(
+        Mock::new("split")
+            .with_ending(|_| TranscriptionResult::new("the lights", true))
+            .with_replies(vec![TranscriptionResult::new("turn on", true)]),
+    ));
+    recognisers.add(Box::new(
+        Mock::new("whole").with_ending(|_| TranscriptionResult::new("turn on the lights", true)),
+    ));
+    recognisers.add(Box::new(
+        Mock::new("other").with_ending(|_| TranscriptionResult::new("turn on the light", true)),
+    ));
+    recognisers.set_ensemble(Some(Combination::Voting), &tx);
+
+    recognisers.begin_utterance();
+    recognisers.speech_to_text(&[0; 160], &tx).unwrap();
+    recognisers.end_utterance(&tx);
+    let results: Vec<_> = rx.try_iter().collect();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].transcription(), "turn on the lights");
+}
@@ -0,0 +1,88 @@
+use super::support::Mock;
+use crate::{
+    evaluation::{edit_distance, evaluate, normalise, Corpus},
+    TranscriptionError, TranscriptionResult,
+};
+
+#[test]
+fn word_and_character_errors() {
+    let reference = normalise("The cat sat on the mat.");
+    assert_eq!(reference, ["the", "cat", "sat", "on", "the", "mat"]);
+    // one substitution, one deletion and one insertion
+    let hypothesis = normalise("the bat sat on mat today");
+    assert_eq!(edit_distance(&reference, &hypothesis), 3);
+    assert_eq!(edit_distance(&reference, &reference), 0);
+    assert_eq!(edit_distance::<&str>(&[], &["a", "b"]), 2);
+    let kitten: Vec<_> = "kitten".chars().collect();
+    let sitting: Vec<_> = "sitting".chars().collect();
+    assert_eq!(edit_distance(&kitten, &sitting), 3);
+    assert_eq!(normalise("Don't STOP-now"), ["don't", "stop", "now"]);
+}
+
+#[test]
+fn backends_are_scored_over_a_corpus() {
+    let directory = std::env::temp_dir().join(format!("kara-corpus-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    for (name, reference) in [("a", "Hello world."), ("b", "hello there world")] {
+        let mut writer =
+            hound::WavWriter::create(directory.join(format!("{name}.wav")), spec).unwrap();
+        for _ in 0..8000 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        std::fs::write(directory.join(format!("{name}.txt")), reference).unwrap();
+    }
+    // no transcript, left out
+    std::fs::copy(directory.join("a.wav"), directory.join("c.wav")).unwrap();
+
+    let corpus = Corpus::load(&directory).unwrap();
+    assert_eq!(corpus.samples().len(), 2);
+    assert_eq!(corpus.samples()[1].reference(), "hello there world");
+
+    let mut created = 0;
+    let evaluation = evaluate(&corpus, |sample_rate| {
+        assert_eq!(sample_rate, 8000.0);
+        created += 1;
+        // hears the same thing in every recording
+        Ok(Box::new(Mock::new("parrot").with_ending(|_| {
+            TranscriptionResult::new("hello world", true)
+        })))
+    });
+    // both recordings share a sample rate and so a backend
+    assert_eq!(created, 1);
+    assert_eq!(evaluation.source(), "parrot");
+    assert_eq!(evaluation.samples()[0].word_error_rate(), 0.0);
+    assert_eq!(evaluation.samples()[1].word_error_rate(), 1.0 / 3.0);
+    assert_eq!(evaluation.word_error_rate(), 1.0 / 5.0);
+    assert_eq!(evaluation.samples()[1].character_error_rate(), 6.0 / 17.0);
+    assert!(evaluation.real_time_factor() >= 0.0);
+    assert_eq!(evaluation.failures(), 0);
+
+    // samples without a backend are scored as missed, not given up on
+    let failed = evaluate(&corpus, |_| {
+        Err(TranscriptionError::LocalModel(String::from("missing")))
+    });
+    assert_eq!(failed.failures(), 2);
+    assert_eq!(failed.word_error_rate(), 1.0);
+
+    // neither is an unreadable recording
+    std::fs::write(directory.join("a.wav"), b"not a wav file").unwrap();
+    let corpus = Corpus::load(&directory).unwrap();
+    let evaluation = evaluate(&corpus, |_| {
+        Ok(Box::new(Mock::new("parrot").with_ending(|_| {
+            TranscriptionResult::new("hello there world", true)
+        })))
+    });
+    assert_eq!(evaluation.failures(), 1);
+    assert!(evaluation.samples()[0].failed());
+    assert_eq!(evaluation.samples()[1].word_error_rate(), 0.0);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
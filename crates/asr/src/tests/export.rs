@@ -0,0 +1,98 @@
+use crate::{
+    export::{export, ExportFormat, Segment},
+    history::{History, HistoryQuery},
+    TranscriptionResult, Word,
+};
+
+fn timed(words: &[(&str, f32, f32)]) -> TranscriptionResult {
+    let text = words.iter().map(|f| f.0).collect::<Vec<_>>().join(" ");
+    TranscriptionResult::new(&text, true).with_words(
+        words
+            .iter()
+            .map(|(word, start, end)| Word::new(word, *start, *end, Some(1.0)))
+            .collect(),
+    )
+}
+
+fn segments() -> Vec<Segment> {
+    Segment::from_results(&[
+        timed(&[("hello", 1.0, 1.4), ("there", 1.5, 2.0)]),
+        TranscriptionResult::new("not final", false),
+        TranscriptionResult::new("a <b> & c", true),
+        timed(&[("bye", 3661.25, 3661.5)]).with_speaker(Vec::new(), Some(String::from("Sam"))),
+    ])
+}
+
+#[test]
+fn subtitles_from_results() {
+    let segments = segments();
+    assert_eq!(segments.len(), 3);
+    // untimed results follow the one before
+    assert_eq!(segments[1].start(), 2.0);
+    assert_eq!(segments[1].end(), 2.0 + 4.0 * 0.4);
+
+    assert_eq!(
+        export(&segments, ExportFormat::Srt),
+        "1\n00:00:01,000 --> 00:00:02,000\nhello there\n\n\
+         2\n00:00:02,000 --> 00:00:03,600\na <b> & c\n\n\
+         3\n01:01:01,250 --> 01:01:01,500\nSam: bye\n\n"
+    );
+    assert_eq!(
+        export(&segments, ExportFormat::WebVtt),
+        "WEBVTT\n\n\
+         00:00:01.000 --> 00:00:02.000\nhello there\n\n\
+         00:00:02.000 --> 00:00:03.600\na &lt;b&gt; &amp; c\n\n\
+         01:01:01.250 --> 01:01:01.500\n<v Sam>bye\n\n"
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(&export(&segments, ExportFormat::Json)).unwrap();
+    let first = &json["segments"][0];
+    assert_eq!(first["text"], "hello there");
+    assert_eq!(first["words"][1]["word"], "there");
+    assert_eq!(first["words"][1]["start"], 1.5);
+    assert!(json["segments"][1].get("words").is_none());
+    assert_eq!(json["segments"][2]["speaker"], "Sam");
+
+    assert_eq!("VTT".parse(), Ok(ExportFormat::WebVtt));
+    assert!("docx".parse::<ExportFormat>().is_err());
+}
+
+#[test]
+fn long_captions_are_wrapped() {
+    let text = "this caption is much too long to be shown on a single line of the screen";
+    let srt = export(
+        &Segment::from_results(&[TranscriptionResult::new(text, true)]),
+        ExportFormat::Srt,
+    );
+    let lines: Vec<_> = srt.lines().skip(2).take_while(|f| !f.is_empty()).collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines.iter().all(|f| f.len() <= 42));
+    assert_eq!(lines.join(" "), text);
+}
+
+#[test]
+fn subtitles_from_history() {
+    let path = std::env::temp_dir().join(format!("kara-export-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut history = History::open(&path).unwrap();
+    history
+        .record(&timed(&[("first", 10.0, 10.5), ("line", 10.5, 11.0)]))
+        .unwrap();
+    history
+        .record(&TranscriptionResult::new("second line", true))
+        .unwrap();
+
+    let entries = history.search(&HistoryQuery::default()).unwrap();
+    let segments = Segment::from_history(&entries);
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].start(), 0.0);
+    assert_eq!(segments[0].end(), 1.0);
+    // words keep their spacing but move onto the history's timeline
+    assert_eq!(segments[0].words()[1].start(), 0.5);
+    assert!(segments[1].end() >= segments[0].end());
+    assert!((segments[1].end() - segments[1].start() - 0.8).abs() < 1e-3);
+    assert_eq!(segments[1].text(), "second line");
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,157 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio_tungstenite::tungstenite::{self, http::Response};
+
+use super::support::Mock;
+use crate::{
+    sources::{BackendState, FallbackPolicy, SpeechRecognisers},
+    Retry, TranscriptionError,
+};
+
+fn flaky(name: &'static str) -> (Mock, Arc<AtomicBool>, Arc<AtomicUsize>) {
+    let source = Mock::new(name);
+    let (failing, calls) = (source.failing(), source.calls());
+    (source, failing, calls)
+}
+
+#[test]
+fn failing_backend_is_skipped_then_recovers() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let (primary, primary_failing, primary_calls) = flaky("primary");
+    let (fallback, _, fallback_calls) = flaky("fallback");
+
+    let mut recognisers = SpeechRecognisers::new();
+    recognisers.set_policy(FallbackPolicy {
+        failure_threshold: 2,
+        initial_backoff: Duration::from_millis(50),
+        max_backoff: Duration::from_secs(1),
+    });
+    recognisers.add(Box::new(primary));
+    recognisers.add(Box::new(fallback));
+
+    primary_failing.store(true, Ordering::SeqCst);
+    recognisers.speech_to_text(&[0; 160], &tx).unwrap();
+    assert_eq!(recognisers.status()[0].state, BackendState::Degraded);
+    recognisers.speech_to_text(&[0; 160], &tx).unwrap();
+    let status = recognisers.status();
+    assert_eq!(status[0].state, BackendState::Unavailable);
+    assert_eq!(status[0].consecutive_failures, 2);
+    assert!(status[0].retry_in.is_some());
+
+    // the open circuit is not tried again until its backoff has passed
+    for _ in 0..5 {
+        recognisers.speech_to_text(&[0; 160], &tx).unwrap();
+    }
+    assert_eq!(primary_calls.load(Ordering::SeqCst), 2);
+    assert_eq!(fallback_calls.load(Ordering::SeqCst), 7);
+
+    primary_failing.store(false, Ordering::SeqCst);
+    std::thread::sleep(Duration::from_millis(60));
+    recognisers.speech_to_text(&[0; 160], &tx).unwrap();
+    let status = recognisers.status();
+    assert_eq!(status[0].state, BackendState::Healthy);
+    assert_eq!(status[0].consecutive_failures, 0);
+    assert_eq!(fallback_calls.load(Ordering::SeqCst), 7);
+}
+
+fn handshake_failure(status: u16, retry_after: Option<&str>) -> TranscriptionError {
+    let mut response = Response::builder().status(status);
+    if let Some(retry_after) = retry_after {
+        response = response.header("Retry-After", retry_after);
+    }
+    TranscriptionError::connection(
+        "wss://example.com",
+        tungstenite::Error::Http(response.body(None).unwrap()),
+    )
+}
+
+#[test]
+fn errors_say_whether_to_retry() {
+    assert!(matches!(
+        handshake_failure(401, None),
+        TranscriptionError::Authentication { .. }
+    ));
+    assert_eq!(handshake_failure(403, None).retry(), Retry::Never);
+    assert_eq!(handshake_failure(404, None).retry(), Retry::Never);
+    assert_eq!(
+        handshake_failure(429, Some("30")).retry(),
+        Retry::After(Duration::from_secs(30))
+    );
+    assert_eq!(handshake_failure(503, None).retry(), Retry::Backoff);
+    let refused =
+        TranscriptionError::connection("ws://localhost:2700", tungstenite::Error::ConnectionClosed);
+    assert!(refused.is_transient());
+    let poisoned = std::sync::Mutex::new(());
+    let _ = std::panic::catch_unwind(|| {
+        let _guard = poisoned.lock().unwrap();
+        panic!("poisoning the lock");
+    });
+    let error = TranscriptionError::from(poisoned.lock().unwrap_err());
+    assert!(matches!(error, TranscriptionError::Poisoned(_)));
+    assert!(error.is_transient());
+}
+
+#[test]
+fn permanent_failures_skip_the_threshold() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let policy = FallbackPolicy {
+        failure_threshold: 3,
+        initial_backoff: Duration::from_millis(50),
+        max_backoff: Duration::from_secs(60),
+    };
+    let (rejected, rejected_failing, _) = flaky("rejected");
+    let rejected = rejected.with_error(|| handshake_failure(401, None));
+    let (throttled, throttled_failing, _) = flaky("throttled");
+    let throttled = throttled.with_error(|| handshake_failure(429, Some("5")));
+    let (fallback, _, fallback_calls) = flaky("fallback");
+    rejected_failing.store(true, Ordering::SeqCst);
+    throttled_failing.store(true, Ordering::SeqCst);
+
+    let mut recognisers = SpeechRecognisers::new();
+    recognisers.set_policy(policy);
+    recognisers.add(Box::new(rejected));
+    recognisers.add(Box::new(throttled));
+    recognisers.add(Box::new(fallback));
+    recognisers.speech_to_text(&[0; 160], &tx).unwrap();
+
+    let status = recognisers.status();
+    assert_eq!(fallback_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(status[0].state, BackendState::Unavailable);
+    assert!(status[0].retry_in.unwrap() > Duration::from_secs(59));
+    assert_eq!(status[1].state, BackendState::Unavailable);
+    let retry_in = status[1].retry_in.unwrap();
+    assert!(retry_in > Duration::from_secs(4) && retry_in <= Duration::from_secs(5));
+}
+
+#[test]
+fn audio_no_backend_takes_is_an_error() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let (primary, primary_failing, _) = flaky("primary");
+    let (fallback, fallback_failing, _) = flaky("fallback");
+    primary_failing.store(true, Ordering::SeqCst);
+    fallback_failing.store(true, Ordering::SeqCst);
+
+    let mut recognisers = SpeechRecognisers::new();
+    recognisers.set_policy(FallbackPolicy {
+        failure_threshold: 1,
+        initial_backoff: Duration::from_secs(60),
+        max_backoff: Duration::from_secs(60),
+    });
+    recognisers.add(Box::new(primary));
+    recognisers.add(Box::new(fallback));
+
+    let failed = recognisers.speech_to_text(&[0; 160], &tx).unwrap_err();
+    assert!(matches!(failed, TranscriptionError::Unavailable(_)));
+    assert!(failed.to_string().contains("fallback"));
+    // both are backing off now
+    assert!(recognisers.speech_to_text(&[0; 160], &tx).is_err());
+
+    let mut empty = SpeechRecognisers::new();
+    assert!(empty.speech_to_text(&[0; 160], &tx).is_err());
+}
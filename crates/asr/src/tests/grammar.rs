@@ -0,0 +1,21 @@
+use crate::RecognitionMode;
+
+#[test]
+fn grammar_from_json_array() {
+    let mode = RecognitionMode::from_grammar(r#"["turn on the lights", "stop"]"#).unwrap();
+    assert_eq!(
+        mode,
+        RecognitionMode::Command(vec!["turn on the lights".into(), "stop".into()])
+    );
+}
+
+#[test]
+fn grammar_from_phrase_list() {
+    let mode =
+        RecognitionMode::from_grammar("# lights\nturn on the lights\n\nstop # halt\n").unwrap();
+    assert_eq!(
+        mode,
+        RecognitionMode::Command(vec!["turn on the lights".into(), "stop".into()])
+    );
+    assert!(RecognitionMode::from_grammar("# nothing here\n").is_err());
+}
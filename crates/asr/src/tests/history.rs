@@ -0,0 +1,93 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    history::{History, HistoryQuery, Retention},
+    TranscriptionResult, Word,
+};
+
+fn utterance(text: &str, confidence: f32) -> TranscriptionResult {
+    TranscriptionResult::new(text, true)
+        .with_words(vec![Word::new(text, 0.0, 1.0, Some(confidence))])
+        .with_source("kara")
+}
+
+fn temporary_history(name: &str) -> History {
+    let path = std::env::temp_dir().join(format!("kara-{name}-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    History::open(path).unwrap()
+}
+
+#[test]
+fn record_and_search_transcripts() {
+    let mut history = temporary_history("history");
+    assert!(history
+        .record(&TranscriptionResult::new("not yet", false))
+        .unwrap()
+        .is_none());
+    let first = history
+        .record(&utterance("turn on the kitchen lights", 0.9))
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.id(), 0);
+    assert_eq!(first.source(), "kara");
+    assert_eq!(first.confidence(), Some(0.9));
+    history
+        .record(&utterance("what is the weather", 0.8))
+        .unwrap();
+    history
+        .record(&utterance("turn off the Kitchen lights", 0.7))
+        .unwrap();
+
+    let query = HistoryQuery {
+        text: Some(String::from("kitchen TURN")),
+        ..Default::default()
+    };
+    let found = history.search(&query).unwrap();
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[1].text(), "turn off the Kitchen lights");
+    let latest = history
+        .search(&HistoryQuery {
+            limit: Some(1),
+            ..query.clone()
+        })
+        .unwrap();
+    assert_eq!(latest, found[1..]);
+
+    let an_hour_ago = SystemTime::now() - Duration::from_secs(60 * 60);
+    let session = Some(history.session().to_owned());
+    let in_range = HistoryQuery {
+        since: Some(an_hour_ago),
+        session: session.clone(),
+        ..Default::default()
+    };
+    assert_eq!(history.search(&in_range).unwrap().len(), 3);
+    let before = HistoryQuery {
+        until: Some(an_hour_ago),
+        ..Default::default()
+    };
+    assert!(history.search(&before).unwrap().is_empty());
+
+    // ids carry on from what is already stored
+    let mut reopened = History::open(history.path()).unwrap();
+    let next = reopened
+        .record(&utterance("goodbye", 0.5))
+        .unwrap()
+        .unwrap();
+    assert_eq!(next.id(), 3);
+
+    assert_eq!(reopened.delete(&query).unwrap(), 2);
+    let remaining = reopened.search(&HistoryQuery::default()).unwrap();
+    let texts: Vec<_> = remaining.iter().map(|f| f.text()).collect();
+    assert_eq!(texts, ["what is the weather", "goodbye"]);
+
+    let removed = reopened
+        .apply_retention(&Retention {
+            max_age_days: Some(1),
+            max_entries: Some(1),
+        })
+        .unwrap();
+    assert_eq!(removed, 1);
+    assert_eq!(reopened.search(&HistoryQuery::default()).unwrap().len(), 1);
+
+    std::fs::remove_file(reopened.path()).unwrap();
+}
@@ -0,0 +1,114 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use super::support::Mock;
+use crate::{
+    sources::language::LanguageIdentifier, Alternative, Transcibe, TranscriptionResult, Word,
+};
+
+// partially hears `text` in every chunk and finalises it with a fixed confidence
+fn scripted(text: &'static str, confidence: f32) -> (Mock, Arc<AtomicUsize>) {
+    let scripted = Mock::new("kara")
+        .with_replies(vec![TranscriptionResult::new(text, false)])
+        .with_ending(move |_| {
+            let words = vec![Word::new(text, 0.0, 1.0, Some(confidence))];
+            TranscriptionResult::new(text, true).with_words(words)
+        });
+    let heard = scripted.heard();
+    (scripted, heard)
+}
+
+#[test]
+fn most_confident_language_transcribes_the_rest() {
+    let (english, english_heard) = scripted("hello", 0.9);
+    let (german, german_heard) = scripted("hallo", 0.4);
+    let identifier = LanguageIdentifier::new(
+        vec![
+            (String::from("en-US"), Box::new(english)),
+            (String::from("de"), Box::new(german)),
+        ],
+        1000.0,
+    )
+    .unwrap();
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    identifier.begin_utterance().unwrap();
+    // still probing, nothing is sent yet
+    identifier.transcribe(&[0; 1000], &tx).unwrap();
+    assert!(rx.try_iter().next().is_none());
+
+    identifier.transcribe(&[0; 1000], &tx).unwrap();
+    identifier.transcribe(&[0; 500], &tx).unwrap();
+    identifier.end_utterance(&tx).unwrap();
+
+    let results: Vec<_> = rx.try_iter().collect();
+    assert!(results.iter().all(|f| f.language() == Some("en-US")));
+    assert_eq!(results.last().unwrap().transcription(), "hello");
+    assert!(results.last().unwrap().finalised());
+    assert_eq!(english_heard.load(Ordering::SeqCst), 2500);
+    assert_eq!(german_heard.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn short_utterances_are_identified_when_they_end() {
+    let (english, _) = scripted("hello", 0.3);
+    let (german, _) = scripted("hallo", 0.8);
+    let identifier = LanguageIdentifier::new(
+        vec![
+            (String::from("en-US"), Box::new(english)),
+            (String::from("de"), Box::new(german)),
+        ],
+        1000.0,
+    )
+    .unwrap();
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    identifier.transcribe(&[0; 200], &tx).unwrap();
+    identifier.end_utterance(&tx).unwrap();
+
+    let results: Vec<_> = rx.try_iter().collect();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].transcription(), "hallo");
+    assert_eq!(results[0].language(), Some("de"));
+}
+
+// finalises `text` the way vosk does with alternatives on, scored as a whole and not per word
+fn with_alternatives(text: &'static str, score: f32) -> Mock {
+    Mock::new("kara").with_ending(move |_| {
+        let words = vec![Word::new(text, 0.0, 1.0, None)];
+        let alternative = Alternative::new("hm", score / 2.0, Vec::new());
+        TranscriptionResult::new(text, true)
+            .with_score(score)
+            .with_words(words)
+            .with_alternatives(vec![alternative])
+    })
+}
+
+#[test]
+fn hypotheses_without_word_confidences_are_scored_whole() {
+    let identifier = LanguageIdentifier::new(
+        vec![
+            (
+                String::from("en-US"),
+                Box::new(with_alternatives("hello", 120.0)),
+            ),
+            (
+                String::from("de"),
+                Box::new(with_alternatives("hallo", 310.0)),
+            ),
+        ],
+        1000.0,
+    )
+    .unwrap();
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    identifier.transcribe(&[0; 200], &tx).unwrap();
+    identifier.end_utterance(&tx).unwrap();
+
+    let results: Vec<_> = rx.try_iter().collect();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].transcription(), "hallo");
+    assert_eq!(results[0].language(), Some("de"));
+}
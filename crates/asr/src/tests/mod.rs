@@ -0,0 +1,16 @@
+mod ensemble;
+mod evaluation;
+mod export;
+mod fallback;
+mod grammar;
+mod history;
+mod language;
+mod model_cache;
+mod recording;
+mod redaction;
+mod speaker;
+mod support;
+mod text;
+mod vocabulary;
+mod vosk_server;
+mod watson;
@@ -0,0 +1,95 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use crate::{
+    sources::kara::{Idle, ModelCache, MAX_IDLE},
+    TranscriptionError,
+};
+
+#[test]
+fn models_are_loaded_once_while_in_use() {
+    let cache = ModelCache::new();
+    let directory = std::env::temp_dir();
+    let mut loads = 0;
+    let mut load = |_: &Path| {
+        loads += 1;
+        Some(loads)
+    };
+
+    let first = cache.get(&directory, &mut load).unwrap();
+    let second = cache.get(&directory.join("."), &mut load).unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(*second, 1);
+
+    drop(first);
+    drop(second);
+    assert_eq!(*cache.get(&directory, &mut load).unwrap(), 2);
+}
+
+#[test]
+fn failed_loads_are_not_cached() {
+    let cache: ModelCache<u8> = ModelCache::new();
+    let path = Path::new("no-such-model");
+    assert!(matches!(
+        cache.get(path, |_| None),
+        Err(TranscriptionError::LocalModel(_))
+    ));
+    assert_eq!(*cache.get(path, |_| Some(7)).unwrap(), 7);
+}
+
+#[test]
+fn loading_one_model_holds_up_no_other() {
+    let cache: Arc<ModelCache<u8>> = Arc::new(ModelCache::new());
+    let _loaded = cache.get(Path::new("loaded-model"), |_| Some(1)).unwrap();
+
+    let (started, loading) = crossbeam_channel::bounded(0);
+    let (finish, finished) = crossbeam_channel::bounded::<()>(0);
+    let slow = {
+        let cache = Arc::clone(&cache);
+        std::thread::spawn(move || {
+            let model = cache.get(Path::new("slow-model"), |_| {
+                started.send(()).unwrap();
+                finished.recv().unwrap();
+                Some(2)
+            });
+            *model.unwrap()
+        })
+    };
+    loading.recv().unwrap();
+
+    let (found, lookup) = crossbeam_channel::bounded(2);
+    {
+        let cache = Arc::clone(&cache);
+        std::thread::spawn(move || {
+            let loaded = cache.get(Path::new("loaded-model"), |_| None).map(|f| *f);
+            let other = cache.get(Path::new("other-model"), |_| Some(3)).map(|f| *f);
+            found.send((loaded.ok(), other.ok())).unwrap();
+        });
+    }
+    let timeout = Duration::from_secs(5);
+    assert_eq!(lookup.recv_timeout(timeout), Ok((Some(1), Some(3))));
+
+    finish.send(()).unwrap();
+    assert_eq!(slow.join().unwrap(), 2);
+}
+
+#[test]
+fn recognisers_given_back_are_taken_again() {
+    let idle = Idle::new();
+    let dictation = None;
+    let commands = Some(vec![String::from("lights on")]);
+    assert_eq!(idle.take(&dictation).unwrap(), None);
+
+    idle.give_back(dictation.clone(), 1);
+    idle.give_back(commands.clone(), 2);
+    assert_eq!(idle.len(), 2);
+    // only one built for the same grammar will do
+    assert_eq!(idle.take(&Some(Vec::new())).unwrap(), None);
+    assert_eq!(idle.take(&commands).unwrap(), Some(2));
+    assert_eq!(idle.take(&dictation).unwrap(), Some(1));
+    assert_eq!(idle.len(), 0);
+
+    for i in 0..MAX_IDLE + 2 {
+        idle.give_back(dictation.clone(), i);
+    }
+    assert_eq!(idle.len(), MAX_IDLE);
+}
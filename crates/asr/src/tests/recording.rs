@@ -0,0 +1,70 @@
+use super::support::Mock;
+use crate::{recording::Recording, sources::SpeechRecognisers, TranscriptionResult};
+
+// says how many samples it heard when the utterance ends
+fn counter() -> Mock {
+    Mock::new("counter").with_ending(|heard| TranscriptionResult::new(&heard.to_string(), true))
+}
+
+#[test]
+fn stereo_24_bit_wav_is_downmixed_and_transcribed() {
+    let path = std::env::temp_dir().join(format!("kara-stereo-{}.wav", std::process::id()));
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: 8000,
+        bits_per_sample: 24,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for _ in 0..4000 {
+        writer.write_sample(0x40_0000).unwrap();
+        writer.write_sample(0).unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let recording = Recording::open_wav(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(recording.sample_rate(), 8000.0);
+    assert_eq!(recording.samples().len(), 4000);
+    assert_eq!(recording.samples()[0], 0x2000);
+    assert_eq!(recording.duration().as_millis(), 500);
+
+    let mut recognisers = SpeechRecognisers::new();
+    recognisers.add(Box::new(counter()));
+    let results = recognisers.transcribe_recording(&recording).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].transcription(), "4000");
+}
+
+#[test]
+fn missing_recording_is_an_error() {
+    assert!(Recording::open_wav("/nonexistent/recording.wav").is_err());
+}
+
+#[test]
+fn recordings_are_resampled_for_backends_at_another_rate() {
+    let path = std::env::temp_dir().join(format!("kara-resampled-{}.wav", std::process::id()));
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for i in 0..4000 {
+        writer.write_sample((i % 100) as i16).unwrap();
+    }
+    writer.finalize().unwrap();
+    let recording = Recording::open_wav(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let mut recognisers = SpeechRecognisers::new();
+    recognisers.add(Box::new(counter().with_rate(16_000.0)));
+    let results = recognisers.transcribe_recording(&recording).unwrap();
+    // twice as many samples, the end included
+    assert_eq!(results[0].transcription(), "8000");
+
+    let resampled = recording.resample(11_025.0);
+    assert_eq!(resampled.sample_rate(), 11_025.0);
+    assert_eq!(resampled.samples().len(), 5513);
+}
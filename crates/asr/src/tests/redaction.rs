@@ -0,0 +1,135 @@
+use crate::{
+    text::{Redaction, Redactor},
+    Alternative, TranscriptionResult, Word,
+};
+
+fn redactor() -> Redactor {
+    Redactor::new(&Redaction {
+        profanity: true,
+        personal_data: true,
+        words: vec![String::from("Voldemort")],
+        patterns: vec![String::from(r"(?i)project \w+"), String::from("(")],
+    })
+}
+
+#[test]
+fn personal_data_is_replaced() {
+    let redactor = redactor();
+    assert_eq!(
+        redactor.redact("mail jane.doe@example.com today"),
+        "mail [email] today"
+    );
+    assert_eq!(
+        redactor.redact("it is jane at example dot com"),
+        "it is [email]"
+    );
+    assert_eq!(
+        redactor.redact("call five five five oh one two three four"),
+        "call [phone number]"
+    );
+    assert_eq!(
+        redactor.redact("call +44 20 7946 0958 now"),
+        "call [phone number] now"
+    );
+    assert_eq!(
+        redactor.redact("card 4111 1111 1111 1111 please"),
+        "card [card number] please"
+    );
+    assert_eq!(redactor.redact("it was 2024"), "it was 2024");
+    assert_eq!(redactor.redact("start project kara"), "start [redacted]");
+}
+
+#[test]
+fn profanity_keeps_its_first_letter() {
+    let redactor = redactor();
+    assert_eq!(
+        redactor.redact("well shit, that broke"),
+        "well s***, that broke"
+    );
+    assert_eq!(redactor.redact("Voldemort did it"), "V******** did it");
+    assert_eq!(redactor.redact("pass the salt"), "pass the salt");
+}
+
+#[test]
+fn partial_results_and_words_are_redacted() {
+    let words = vec![
+        Word::new("call", 0.0, 0.2, None),
+        Word::new("five", 0.2, 0.4, None),
+        Word::new("five", 0.4, 0.6, None),
+        Word::new("five", 0.6, 0.8, None),
+        Word::new("one", 0.8, 1.0, None),
+        Word::new("two", 1.0, 1.2, None),
+        Word::new("three", 1.2, 1.4, None),
+        Word::new("four", 1.4, 1.6, None),
+    ];
+    let result =
+        TranscriptionResult::new("call five five five one two three four", false).with_words(words);
+    let result = redactor().apply(result);
+    assert_eq!(result.transcription(), "call [phone number]");
+    assert_eq!(result.words()[0].word(), "call");
+    assert!(result.words()[1..].iter().all(|f| f.word() == "[redacted]"));
+}
+
+fn timed(text: &str) -> Vec<Word> {
+    text.split_whitespace()
+        .enumerate()
+        .map(|(i, f)| Word::new(f, i as f32 * 0.2, (i + 1) as f32 * 0.2, None))
+        .collect()
+}
+
+fn words(result: &[Word]) -> Vec<&str> {
+    result.iter().map(Word::word).collect()
+}
+
+#[test]
+fn spoken_email_is_masked_in_the_words() {
+    let text = "write to John dot Smith at gmail dot com please";
+    let result = TranscriptionResult::new(text, true).with_words(timed(text));
+    let result = redactor().apply(result);
+    assert_eq!(result.transcription(), "write to [email] please");
+    let mut expected = vec!["write", "to"];
+    expected.extend(["[redacted]"; 7]);
+    expected.push("please");
+    assert_eq!(words(result.words()), expected);
+}
+
+#[test]
+fn spoken_phone_number_is_masked_in_the_words_of_alternatives() {
+    let text = "ring one two three four five six seven on two";
+    let alternative = Alternative::new(text, 0.4, timed(text));
+    let result = TranscriptionResult::new("ring one two three on two", true)
+        .with_words(timed("ring one two three on two"))
+        .with_alternatives(vec![alternative]);
+    let result = redactor().apply(result);
+    // too short to be a phone number
+    assert_eq!(result.transcription(), "ring one two three on two");
+    assert_eq!(
+        words(result.words()),
+        ["ring", "one", "two", "three", "on", "two"]
+    );
+    let alternative = &result.alternatives()[0];
+    assert_eq!(alternative.transcription(), "ring [phone number] on two");
+    let mut expected = vec!["ring"];
+    expected.extend(["[redacted]"; 7]);
+    expected.extend(["on", "two"]);
+    assert_eq!(words(alternative.words()), expected);
+}
+
+#[test]
+fn profanity_leaves_other_words_alone() {
+    let text = "shit I owe you two";
+    let result = TranscriptionResult::new(text, true).with_words(timed(text));
+    let result = redactor().apply(result);
+    assert_eq!(result.transcription(), "s*** I owe you two");
+    assert_eq!(words(result.words()), ["s***", "I", "owe", "you", "two"]);
+}
+
+#[test]
+fn nothing_is_redacted_by_default() {
+    let redactor = Redactor::new(&Redaction::default());
+    assert!(redactor.is_empty());
+    assert_eq!(
+        redactor.redact("shit, call 555 0123 456"),
+        "shit, call 555 0123 456"
+    );
+}
@@ -0,0 +1,51 @@
+use crate::speaker::SpeakerProfiles;
+
+#[test]
+fn enrol_identify_and_reload_profiles() {
+    let directory = std::env::temp_dir().join(format!("kara-speakers-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let mut profiles = SpeakerProfiles::load(&directory).unwrap();
+    assert!(profiles.profiles().is_empty());
+    profiles.enrol("alice", &[1.0, 0.0, 0.0]).unwrap();
+    profiles.enrol("alice", &[0.8, 0.2, 0.0]).unwrap();
+    profiles.enrol("bob", &[0.0, 0.0, 1.0]).unwrap();
+    assert!(profiles.enrol("../eve", &[1.0, 0.0, 0.0]).is_err());
+
+    let profiles = SpeakerProfiles::load(&directory).unwrap();
+    let (alice, _) = profiles.identify(&[0.9, 0.1, 0.1]).unwrap();
+    assert_eq!(alice.name(), "alice");
+    assert_eq!(alice.utterances(), 2);
+    assert_eq!(alice.embedding(), &[0.9, 0.1, 0.0]);
+    assert_eq!(profiles.identify(&[0.0, 0.1, 0.9]).unwrap().0.name(), "bob");
+    assert!(profiles.identify(&[0.0, 1.0, 0.0]).is_none());
+
+    let mut profiles = profiles;
+    assert!(profiles.remove("bob").unwrap());
+    assert!(!profiles.remove("bob").unwrap());
+    assert_eq!(
+        SpeakerProfiles::load(&directory).unwrap().profiles().len(),
+        1
+    );
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn unreadable_profiles_are_skipped() {
+    let directory =
+        std::env::temp_dir().join(format!("kara-speakers-corrupt-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    SpeakerProfiles::load(&directory)
+        .unwrap()
+        .enrol("alice", &[1.0, 0.0])
+        .unwrap();
+    std::fs::write(directory.join("bob.json"), "{\"name\": \"bob\"").unwrap();
+
+    let profiles = SpeakerProfiles::load(&directory).unwrap();
+    assert_eq!(profiles.profiles().len(), 1);
+    assert_eq!(profiles.profiles()[0].name(), "alice");
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
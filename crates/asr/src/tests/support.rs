@@ -0,0 +1,145 @@
+use std::{
+    future::Future,
+    net::{SocketAddr, TcpListener},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crossbeam_channel::Sender;
+
+use crate::{Result, Transcibe, TranscriptionError, TranscriptionResult};
+
+type Ending = Box<dyn Fn(usize) -> TranscriptionResult + Send>;
+
+/// A backend that answers from a script and keeps count of how it is used
+pub(super) struct Mock {
+    name: &'static str,
+    sample_rate: Option<f32>,
+    // sent for every chunk
+    replies: Vec<TranscriptionResult>,
+    // what an utterance is finalised as, given how many samples were heard since the last reset
+    ending: Option<Ending>,
+    error: fn() -> TranscriptionError,
+    failing: Arc<AtomicBool>,
+    calls: Arc<AtomicUsize>,
+    heard: Arc<AtomicUsize>,
+}
+
+impl Mock {
+    pub(super) fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            sample_rate: None,
+            replies: Vec::new(),
+            ending: None,
+            error: || TranscriptionError::remote("unreachable"),
+            failing: Arc::default(),
+            calls: Arc::default(),
+            heard: Arc::default(),
+        }
+    }
+
+    pub(super) fn with_rate(mut self, sample_rate: f32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    pub(super) fn with_replies(mut self, replies: Vec<TranscriptionResult>) -> Self {
+        self.replies = replies;
+        self
+    }
+
+    pub(super) fn with_ending(
+        mut self,
+        ending: impl Fn(usize) -> TranscriptionResult + Send + 'static,
+    ) -> Self {
+        self.ending = Some(Box::new(ending));
+        self
+    }
+
+    /// What `transcribe` fails with while `failing` is set
+    pub(super) fn with_error(mut self, error: fn() -> TranscriptionError) -> Self {
+        self.error = error;
+        self
+    }
+
+    pub(super) fn failing(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.failing)
+    }
+
+    /// Calls to `transcribe`, failed ones included
+    pub(super) fn calls(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.calls)
+    }
+
+    pub(super) fn heard(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.heard)
+    }
+}
+
+impl Transcibe for Mock {
+    fn source(&self) -> &str {
+        self.name
+    }
+
+    fn sample_rate(&self) -> Option<f32> {
+        self.sample_rate
+    }
+
+    fn transcribe(
+        &self,
+        stream: &[i16],
+        result_sender: &Sender<TranscriptionResult>,
+    ) -> Result<()> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if self.failing.load(Ordering::SeqCst) {
+            return Err((self.error)());
+        }
+        self.heard.fetch_add(stream.len(), Ordering::SeqCst);
+        for reply in &self.replies {
+            let _ = result_sender.send(reply.clone());
+        }
+        Ok(())
+    }
+
+    fn end_utterance(&self, result_sender: &Sender<TranscriptionResult>) -> Result<()> {
+        if let Some(ending) = &self.ending {
+            let _ = result_sender.send(ending(self.heard.load(Ordering::SeqCst)));
+        }
+        Ok(())
+    }
+
+    fn reset(&self) -> Result<()> {
+        self.heard.store(0, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Serves connections on a local port one after another, with `session` speaking for the service
+pub(super) fn stand_in<F, S>(session: F) -> SocketAddr
+where
+    F: Fn(tokio::net::TcpStream) -> S + Send + 'static,
+    S: Future<Output = ()>,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let address = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                session(stream).await;
+            }
+        });
+    });
+
+    address
+}
@@ -0,0 +1,84 @@
+use crate::{text::TextProcessing, TranscriptionResult};
+
+fn written(text: &str) -> String {
+    TextProcessing::default().process(text)
+}
+
+#[test]
+fn numbers_are_written_as_figures() {
+    assert_eq!(
+        written("set a timer for twenty five minutes"),
+        "Set a timer for 25 minutes."
+    );
+    assert_eq!(written("wait five seconds"), "Wait 5 seconds.");
+    assert_eq!(written("one of them is fine"), "One of them is fine.");
+    assert_eq!(
+        written("it weighs one hundred and five point two grams"),
+        "It weighs 105.2 grams."
+    );
+    assert_eq!(
+        written("there were twelve thousand three hundred people"),
+        "There were 12,300 people."
+    );
+    assert_eq!(written("she came twenty first"), "She came 21st.");
+    assert_eq!(written("the first time"), "The first time.");
+}
+
+#[test]
+fn money_and_percentages() {
+    assert_eq!(
+        written("it costs twenty five dollars and fifty cents"),
+        "It costs $25.50."
+    );
+    assert_eq!(written("send three pounds"), "Send £3.");
+    assert_eq!(
+        written("rates rose by two point five percent"),
+        "Rates rose by 2.5%."
+    );
+}
+
+#[test]
+fn dates_and_times() {
+    assert_eq!(
+        written("the meeting is on march twenty first twenty twenty four"),
+        "The meeting is on March 21, 2024."
+    );
+    assert_eq!(
+        written("remind me on the third of may"),
+        "Remind me on May 3."
+    );
+    assert_eq!(written("so may one of us"), "So may one of us.");
+    assert_eq!(
+        written("wake me at seven thirty a m"),
+        "Wake me at 7:30 am."
+    );
+    assert_eq!(written("lunch at twelve o'clock"), "Lunch at 12:00.");
+    assert_eq!(written("call at nine oh five"), "Call at 9:05.");
+    assert_eq!(written("it was nineteen ninety nine"), "It was 1999.");
+}
+
+#[test]
+fn questions_and_casing() {
+    assert_eq!(
+        written("what time is it on monday"),
+        "What time is it on Monday?"
+    );
+    assert_eq!(written("i think i'm done"), "I think I'm done.");
+}
+
+#[test]
+fn steps_can_be_switched_off() {
+    let processing = TextProcessing {
+        punctuation: false,
+        truecasing: true,
+        inverse_normalisation: false,
+    };
+    let result = TranscriptionResult::new("add twenty apples", true);
+    assert_eq!(
+        processing.apply(result).transcription(),
+        "Add twenty apples"
+    );
+
+    let partial = TranscriptionResult::new("add twenty", false);
+    assert_eq!(processing.apply(partial).transcription(), "add twenty");
+}
@@ -0,0 +1,56 @@
+use crate::{
+    vocabulary::{Phrase, Replacement, Vocabulary},
+    Alternative, TranscriptionResult, Word,
+};
+
+fn vocabulary() -> Vocabulary {
+    Vocabulary {
+        phrases: vec![Phrase {
+            phrase: String::from("Grafana"),
+            boost: 1.5,
+        }],
+        replacements: vec![Replacement {
+            from: String::from("graph ana"),
+            to: String::from("Grafana"),
+        }],
+    }
+}
+
+#[test]
+fn boosted_phrases_move_up() {
+    let mut alternatives = vec![
+        Alternative::new("open graph ana", 210.0, Vec::new()),
+        Alternative::new("open graph anna", 208.0, Vec::new()),
+        Alternative::new("open grafana", 205.0, Vec::new()),
+        Alternative::new("opens grafana", 201.0, Vec::new()),
+    ];
+    vocabulary().rescore(&mut alternatives);
+    let order: Vec<_> = alternatives.iter().map(|f| f.transcription()).collect();
+    // one place up overtakes, two places up would need a larger boost
+    assert_eq!(
+        order,
+        [
+            "open graph ana",
+            "open grafana",
+            "open graph anna",
+            "opens grafana"
+        ]
+    );
+}
+
+#[test]
+fn replacements_merge_words() {
+    let words = vec![
+        Word::new("open", 0.0, 0.4, Some(0.9)),
+        Word::new("graph", 0.5, 0.8, Some(0.7)),
+        Word::new("Ana", 0.8, 1.1, Some(0.6)),
+    ];
+    let result = TranscriptionResult::new("open graph Ana", true).with_words(words);
+    let result = vocabulary().replace(result);
+    assert_eq!(result.transcription(), "open Grafana");
+    assert_eq!(result.words().len(), 2);
+    assert_eq!(result.words()[1].word(), "Grafana");
+    assert_eq!(result.words()[1].start(), 0.5);
+    assert_eq!(result.words()[1].end(), 1.1);
+    assert_eq!(result.words()[1].confidence(), Some(0.6));
+}
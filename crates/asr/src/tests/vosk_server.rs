@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::Message;
+
+use super::support;
+use crate::{sources::vosk_server::VoskServerRecogniser, RecognitionMode, Transcibe};
+
+// Answers like vosk-server: one reply per chunk, a final result and a close after end of stream
+fn stand_in() -> String {
+    let address = support::stand_in(|stream| async move {
+        let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+        let mut chunks = 0;
+        let mut phrases = false;
+        while let Some(Ok(message)) = socket.next().await {
+            let reply = match message {
+                Message::Text(text) if text.contains("\"config\"") => {
+                    assert!(text.contains("\"sample_rate\":16000"));
+                    // the same grammar a local model would be given
+                    phrases = text.contains(r#""phrase_list":["lights on","[unk]"]"#);
+                    continue;
+                }
+                Message::Text(text) if text.contains("eof") => {
+                    let last = if phrases {
+                        r#"{"text": "lights on"}"#
+                    } else {
+                        r#"{"text": "goodbye", "result": [{"conf": 1.0, "start": 0.0, "end": 0.4, "word": "goodbye"}]}"#
+                    };
+                    socket.send(Message::Text(last.to_owned())).await.unwrap();
+                    let _ = socket.close(None).await;
+                    break;
+                }
+                Message::Binary(_) => {
+                    chunks += 1;
+                    if chunks == 1 {
+                        r#"{"partial": "hello"}"#
+                    } else {
+                        r#"{"result": [{"conf": 0.8, "start": 0.1, "end": 0.4, "word": "hello"}, {"conf": 1.0, "start": 0.5, "end": 0.9, "word": "world"}], "text": "hello world"}"#
+                    }
+                }
+                _ => continue,
+            };
+            socket.send(Message::Text(reply.to_owned())).await.unwrap();
+        }
+    });
+
+    format!("ws://{address}")
+}
+
+#[test]
+fn vosk_server_partial_and_final_results() {
+    let recogniser = VoskServerRecogniser::new(stand_in(), 16000.0, 0).unwrap();
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let mut results = vec![];
+    for _ in 0..50 {
+        recogniser.transcribe(&[0; 320], &tx).unwrap();
+        results.extend(rx.try_iter());
+        if results.iter().any(|f| f.finalised()) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    assert_eq!(results[0].transcription(), "hello");
+    assert!(!results[0].finalised());
+    let last = results.last().unwrap();
+    assert_eq!(last.transcription(), "hello world");
+    assert!(last.finalised());
+    assert_eq!(last.words()[1].start(), 0.5);
+    assert_eq!(last.confidence(), Some(0.9));
+}
+
+#[test]
+fn vosk_server_end_utterance_flushes_final_result() {
+    let recogniser = VoskServerRecogniser::new(stand_in(), 16000.0, 0).unwrap();
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    recogniser.transcribe(&[0; 320], &tx).unwrap();
+    recogniser.end_utterance(&tx).unwrap();
+    let last = rx.try_iter().last().unwrap();
+    assert_eq!(last.transcription(), "goodbye");
+    assert!(last.finalised());
+
+    // the next utterance reconnects with the phrase list
+    recogniser
+        .set_mode(&RecognitionMode::Command(vec!["Lights On".into()]))
+        .unwrap();
+    recogniser.transcribe(&[0; 320], &tx).unwrap();
+    recogniser.end_utterance(&tx).unwrap();
+    assert_eq!(rx.try_iter().last().unwrap().transcription(), "lights on");
+}
+
+#[test]
+fn vosk_server_needs_websocket_url() {
+    assert!(VoskServerRecogniser::new("http://localhost:2700", 16000.0, 0).is_err());
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    Message,
+};
+
+use super::support;
+use crate::{sources::watson::WatsonRecogniser, Transcibe};
+
+#[allow(clippy::result_large_err)]
+fn check_handshake(request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+    assert_eq!(request.uri().path(), "/instances/test/v1/recognize");
+    assert!(request.headers().contains_key("Authorization"));
+    Ok(response)
+}
+
+// Speaks just enough of the recognize protocol to answer one utterance
+fn stand_in() -> String {
+    let address = support::stand_in(|stream| async move {
+        let mut socket = tokio_tungstenite::accept_hdr_async(stream, check_handshake)
+            .await
+            .unwrap();
+
+        let mut chunks = 0;
+        while let Some(Ok(message)) = socket.next().await {
+            let reply = match message {
+                Message::Text(text) if text.contains("\"start\"") => {
+                    assert!(text.contains("audio/l16;rate=16000"));
+                    r#"{"state": "listening"}"#
+                }
+                Message::Text(text) if text.contains("\"stop\"") => {
+                    let last = r#"{"result_index": 1, "results": [{"final": true, "alternatives": [{"transcript": "goodbye "}]}]}"#;
+                    socket.send(Message::Text(last.to_owned())).await.unwrap();
+                    r#"{"state": "listening"}"#
+                }
+                Message::Binary(_) => {
+                    chunks += 1;
+                    if chunks == 1 {
+                        r#"{"result_index": 0, "results": [{"final": false, "alternatives": [{"transcript": "hello "}]}]}"#
+                    } else {
+                        r#"{"result_index": 0, "results": [{"final": true, "alternatives": [{"transcript": "hello world ", "confidence": 0.9, "timestamps": [["hello", 0.1, 0.4], ["world", 0.5, 0.9]], "word_confidence": [["hello", 0.8], ["world", 1.0]]}]}]}"#
+                    }
+                }
+                _ => continue,
+            };
+            socket.send(Message::Text(reply.to_owned())).await.unwrap();
+        }
+    });
+
+    format!("http://{address}/instances/test")
+}
+
+#[test]
+fn watson_interim_and_final_results() {
+    let recogniser = WatsonRecogniser::new("key", stand_in(), 16000.0).unwrap();
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let mut results = vec![];
+    for _ in 0..50 {
+        recogniser.transcribe(&[0; 320], &tx).unwrap();
+        results.extend(rx.try_iter());
+        if results.iter().any(|f| f.finalised()) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    assert_eq!(results[0].transcription(), "hello");
+    assert!(!results[0].finalised());
+    let last = results.last().unwrap();
+    assert_eq!(last.transcription(), "hello world");
+    assert!(last.finalised());
+    assert_eq!(last.words().len(), 2);
+    assert_eq!(last.words()[1].word(), "world");
+    assert_eq!(last.words()[1].start(), 0.5);
+    assert_eq!(last.confidence(), Some(0.9));
+}
+
+#[test]
+fn watson_rejects_unknown_scheme() {
+    assert!(WatsonRecogniser::new("key", "ftp://localhost", 16000.0).is_err());
+}
+
+#[test]
+fn watson_end_utterance_flushes_final_result() {
+    let recogniser = WatsonRecogniser::new("key", stand_in(), 16000.0).unwrap();
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    recogniser.begin_utterance().unwrap();
+    recogniser.transcribe(&[0; 320], &tx).unwrap();
+    recogniser.end_utterance(&tx).unwrap();
+
+    let last = rx.try_iter().last().unwrap();
+    assert_eq!(last.transcription(), "goodbye");
+    assert!(last.finalised());
+}
@@ -0,0 +1,145 @@
+mod numbers;
+mod redaction;
+
+pub use redaction::{Redaction, Redactor};
+
+use serde::{Deserialize, Serialize};
+
+use crate::TranscriptionResult;
+
+/// Turns what a recogniser heard into written text. Every step is rule based and English only
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TextProcessing {
+    /// Ends every utterance with a full stop, or a question mark when it opens like a question
+    #[serde(default = "enabled")]
+    pub punctuation: bool,
+
+    /// Capitalises sentences, "I", months, days and languages
+    #[serde(default = "enabled")]
+    pub truecasing: bool,
+
+    /// Writes spoken numbers, dates, times, currencies and percentages as figures
+    #[serde(rename = "inverse-normalisation")]
+    #[serde(default = "enabled")]
+    pub inverse_normalisation: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for TextProcessing {
+    fn default() -> Self {
+        Self {
+            punctuation: true,
+            truecasing: true,
+            inverse_normalisation: true,
+        }
+    }
+}
+
+const QUESTION_WORDS: &[&str] = &[
+    "what", "who", "whom", "whose", "where", "when", "why", "how", "which", "is", "are", "am",
+    "was", "were", "can", "could", "would", "will", "shall", "should", "do", "does", "did", "have",
+    "has", "may", "might",
+];
+
+const PROPER_NOUNS: &[&str] = &[
+    "i",
+    "i'm",
+    "i'll",
+    "i've",
+    "i'd",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+    "january",
+    "february",
+    "march",
+    "april",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+    "english",
+    "german",
+    "french",
+    "spanish",
+    "italian",
+    "portuguese",
+    "russian",
+    "chinese",
+    "japanese",
+    "hindi",
+];
+
+impl TextProcessing {
+    pub fn process(&self, text: &str) -> String {
+        let mut text = text.trim().to_owned();
+        if text.is_empty() {
+            return text;
+        }
+        if self.inverse_normalisation {
+            text = numbers::inverse_normalise(&text);
+        }
+        if self.truecasing {
+            text = truecase(&text);
+        }
+        if self.punctuation {
+            text = punctuate(&text);
+        }
+        text
+    }
+
+    /// Rewrites the text of a finalised result and its alternatives, words keep what was heard
+    pub fn apply(&self, mut result: TranscriptionResult) -> TranscriptionResult {
+        if !result.finalised {
+            return result;
+        }
+        result.text = self.process(&result.text);
+        for alternative in result.alternatives.iter_mut() {
+            alternative.text = self.process(&alternative.text);
+        }
+        result
+    }
+}
+
+fn truecase(text: &str) -> String {
+    let mut sentence_start = true;
+    text.split_whitespace()
+        .map(|word| {
+            // "may" is left alone, it is more often a verb than the month
+            let cased = if sentence_start || PROPER_NOUNS.contains(&word.to_lowercase().as_str()) {
+                numbers::capitalise(word)
+            } else {
+                word.to_owned()
+            };
+            sentence_start = word.ends_with(['.', '?', '!']);
+            cased
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn punctuate(text: &str) -> String {
+    if text.ends_with(['.', '?', '!']) {
+        return text.to_owned();
+    }
+    let first = text
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if QUESTION_WORDS.contains(&first.as_str()) {
+        format!("{text}?")
+    } else {
+        format!("{text}.")
+    }
+}
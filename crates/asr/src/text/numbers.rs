@@ -0,0 +1,503 @@
+// Inverse text normalisation for English: spoken numbers, dates, times, currencies and
+// percentages in their written form
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Unit,
+    Teen,
+    Tens,
+    Hundred,
+    Scale,
+}
+
+const CARDINALS: &[(&str, Kind, u64)] = &[
+    ("zero", Kind::Unit, 0),
+    ("one", Kind::Unit, 1),
+    ("two", Kind::Unit, 2),
+    ("three", Kind::Unit, 3),
+    ("four", Kind::Unit, 4),
+    ("five", Kind::Unit, 5),
+    ("six", Kind::Unit, 6),
+    ("seven", Kind::Unit, 7),
+    ("eight", Kind::Unit, 8),
+    ("nine", Kind::Unit, 9),
+    ("ten", Kind::Teen, 10),
+    ("eleven", Kind::Teen, 11),
+    ("twelve", Kind::Teen, 12),
+    ("thirteen", Kind::Teen, 13),
+    ("fourteen", Kind::Teen, 14),
+    ("fifteen", Kind::Teen, 15),
+    ("sixteen", Kind::Teen, 16),
+    ("seventeen", Kind::Teen, 17),
+    ("eighteen", Kind::Teen, 18),
+    ("nineteen", Kind::Teen, 19),
+    ("twenty", Kind::Tens, 20),
+    ("thirty", Kind::Tens, 30),
+    ("forty", Kind::Tens, 40),
+    ("fifty", Kind::Tens, 50),
+    ("sixty", Kind::Tens, 60),
+    ("seventy", Kind::Tens, 70),
+    ("eighty", Kind::Tens, 80),
+    ("ninety", Kind::Tens, 90),
+    ("hundred", Kind::Hundred, 100),
+    ("thousand", Kind::Scale, 1_000),
+    ("million", Kind::Scale, 1_000_000),
+    ("billion", Kind::Scale, 1_000_000_000),
+];
+
+const ORDINALS: &[(&str, Kind, u64)] = &[
+    ("first", Kind::Unit, 1),
+    ("second", Kind::Unit, 2),
+    ("third", Kind::Unit, 3),
+    ("fourth", Kind::Unit, 4),
+    ("fifth", Kind::Unit, 5),
+    ("sixth", Kind::Unit, 6),
+    ("seventh", Kind::Unit, 7),
+    ("eighth", Kind::Unit, 8),
+    ("ninth", Kind::Unit, 9),
+    ("tenth", Kind::Teen, 10),
+    ("eleventh", Kind::Teen, 11),
+    ("twelfth", Kind::Teen, 12),
+    ("thirteenth", Kind::Teen, 13),
+    ("fourteenth", Kind::Teen, 14),
+    ("fifteenth", Kind::Teen, 15),
+    ("sixteenth", Kind::Teen, 16),
+    ("seventeenth", Kind::Teen, 17),
+    ("eighteenth", Kind::Teen, 18),
+    ("nineteenth", Kind::Teen, 19),
+    ("twentieth", Kind::Tens, 20),
+    ("thirtieth", Kind::Tens, 30),
+    ("fortieth", Kind::Tens, 40),
+    ("fiftieth", Kind::Tens, 50),
+    ("sixtieth", Kind::Tens, 60),
+    ("seventieth", Kind::Tens, 70),
+    ("eightieth", Kind::Tens, 80),
+    ("ninetieth", Kind::Tens, 90),
+    ("hundredth", Kind::Hundred, 100),
+    ("thousandth", Kind::Scale, 1_000),
+    ("millionth", Kind::Scale, 1_000_000),
+];
+
+const MONTHS: &[&str] = &[
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+const CURRENCIES: &[(&str, &str, &[&str])] = &[
+    ("$", "dollar", &["cent", "cents"]),
+    ("$", "dollars", &["cent", "cents"]),
+    ("£", "pound", &["penny", "pence"]),
+    ("£", "pounds", &["penny", "pence"]),
+    ("€", "euro", &["cent", "cents"]),
+    ("€", "euros", &["cent", "cents"]),
+];
+
+// a lone digit word is only written as a number in front of one of these
+const MEASURES: &[&str] = &[
+    "second",
+    "seconds",
+    "minute",
+    "minutes",
+    "hour",
+    "hours",
+    "day",
+    "days",
+    "week",
+    "weeks",
+    "month",
+    "months",
+    "year",
+    "years",
+    "degree",
+    "degrees",
+    "percent",
+    "metre",
+    "metres",
+    "meter",
+    "meters",
+    "kilometre",
+    "kilometres",
+    "kilometer",
+    "kilometers",
+    "mile",
+    "miles",
+    "gram",
+    "grams",
+    "kilogram",
+    "kilograms",
+    "litre",
+    "litres",
+    "liter",
+    "liters",
+];
+
+struct Number {
+    value: u64,
+    // digits after "point", as spoken
+    decimals: String,
+    ordinal: bool,
+    // words spoken, the value alone does not say whether "one" or "one hundred" was heard
+    len: usize,
+}
+
+impl Number {
+    fn written(&self) -> String {
+        let mut written = grouped(self.value);
+        if !self.decimals.is_empty() {
+            written.push('.');
+            written.push_str(&self.decimals);
+        }
+        if self.ordinal {
+            written.push_str(ordinal_suffix(self.value));
+        }
+        written
+    }
+}
+
+pub(super) fn inverse_normalise(text: &str) -> String {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let mut written = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        match date(&tokens, i)
+            .or_else(|| time(&tokens, i))
+            .or_else(|| {
+                paired_year(&tokens, i)
+                    .filter(|(f, _)| (1500..2100).contains(f))
+                    .map(|(year, len)| (year.to_string(), len))
+            })
+            .or_else(|| quantity(&tokens, i))
+        {
+            Some((text, len)) => {
+                written.push(text);
+                i += len;
+            }
+            None => {
+                written.push(tokens[i].to_owned());
+                i += 1;
+            }
+        }
+    }
+    written.join(" ")
+}
+
+fn word(tokens: &[&str], i: usize) -> Option<String> {
+    tokens.get(i).map(|f| f.to_lowercase())
+}
+
+fn lookup(table: &[(&str, Kind, u64)], word: &str) -> Option<(Kind, u64)> {
+    table
+        .iter()
+        .find(|(f, _, _)| *f == word)
+        .map(|(_, kind, value)| (*kind, *value))
+}
+
+fn is_number_word(word: &str) -> bool {
+    lookup(CARDINALS, word).is_some() || lookup(ORDINALS, word).is_some()
+}
+
+// the longest run of words from `start` that makes a single number
+fn number(tokens: &[&str], start: usize) -> Option<Number> {
+    let mut total = 0;
+    let mut current = 0;
+    let mut last: Option<Kind> = None;
+    let mut last_scale = u64::MAX;
+    let mut ordinal = false;
+    let mut end = start;
+    let mut i = start;
+    while let Some(spoken) = word(tokens, i) {
+        // "one hundred and five"
+        if spoken == "and" && matches!(last, Some(Kind::Hundred | Kind::Scale)) {
+            match word(tokens, i + 1) {
+                Some(next) if is_number_word(&next) => {
+                    i += 1;
+                    continue;
+                }
+                _ => break,
+            }
+        }
+        let (kind, value, is_ordinal) = match lookup(CARDINALS, &spoken) {
+            Some((kind, value)) => (kind, value, false),
+            None => match lookup(ORDINALS, &spoken) {
+                Some((kind, value)) => (kind, value, true),
+                None => break,
+            },
+        };
+        let low = current % 100;
+        let allowed = match kind {
+            Kind::Unit => match last {
+                None | Some(Kind::Hundred | Kind::Scale) => true,
+                Some(Kind::Tens) => low % 10 == 0 && value > 0,
+                _ => false,
+            },
+            Kind::Teen | Kind::Tens => {
+                matches!(last, None | Some(Kind::Hundred | Kind::Scale)) && low == 0
+            }
+            Kind::Hundred => {
+                matches!(last, Some(Kind::Unit | Kind::Teen | Kind::Tens)) && current < 100
+            }
+            Kind::Scale => last.is_some() && current > 0 && value < last_scale,
+        };
+        if !allowed {
+            break;
+        }
+        match kind {
+            Kind::Unit | Kind::Teen | Kind::Tens => current += value,
+            Kind::Hundred => current *= 100,
+            Kind::Scale => {
+                total += current * value;
+                current = 0;
+                last_scale = value;
+            }
+        }
+        last = Some(kind);
+        i += 1;
+        end = i;
+        if is_ordinal {
+            ordinal = true;
+            break;
+        }
+    }
+    if end == start {
+        return None;
+    }
+
+    // "three point one four"
+    let mut decimals = String::new();
+    if !ordinal && word(tokens, end).as_deref() == Some("point") {
+        let mut i = end + 1;
+        while let Some((Kind::Unit, value)) = word(tokens, i).and_then(|f| lookup(CARDINALS, &f)) {
+            decimals.push_str(&value.to_string());
+            i += 1;
+        }
+        if !decimals.is_empty() {
+            end = i;
+        }
+    }
+    Some(Number {
+        value: total + current,
+        decimals,
+        ordinal,
+        len: end - start,
+    })
+}
+
+// numbers, money and percentages
+fn quantity(tokens: &[&str], start: usize) -> Option<(String, usize)> {
+    let amount = number(tokens, start)?;
+    let mut end = start + amount.len;
+    let next = word(tokens, end);
+
+    if !amount.ordinal {
+        if let Some((symbol, _, minor)) = CURRENCIES
+            .iter()
+            .find(|(_, f, _)| next.as_deref() == Some(*f))
+        {
+            end += 1;
+            let mut written = format!("{symbol}{}", amount.written());
+            // "five dollars and twenty cents"
+            if amount.decimals.is_empty() && word(tokens, end).as_deref() == Some("and") {
+                if let Some(fraction) = number(tokens, end + 1) {
+                    let unit = word(tokens, end + 1 + fraction.len);
+                    if fraction.value < 100
+                        && fraction.decimals.is_empty()
+                        && !fraction.ordinal
+                        && unit.is_some_and(|f| minor.contains(&f.as_str()))
+                    {
+                        written.push_str(&format!(".{:02}", fraction.value));
+                        end += fraction.len + 2;
+                    }
+                }
+            }
+            return Some((written, end - start));
+        }
+        if next.as_deref() == Some("percent") {
+            return Some((format!("{}%", amount.written()), amount.len + 1));
+        }
+    }
+
+    // "the first time" and "one of them" stay as they are
+    let lone_digit = amount.len == 1 && amount.value < 10;
+    if lone_digit && (amount.ordinal || !next.is_some_and(|f| MEASURES.contains(&f.as_str()))) {
+        return None;
+    }
+    Some((amount.written(), amount.len))
+}
+
+// "march twenty first twenty twenty four" or "the first of may"
+fn date(tokens: &[&str], start: usize) -> Option<(String, usize)> {
+    let (month, day, mut end) = match month(tokens, start) {
+        Some(month) => {
+            let day = number(tokens, start + 1)?;
+            let end = start + 1 + day.len;
+            (month, day, end)
+        }
+        None if word(tokens, start).as_deref() == Some("the") => {
+            let day = number(tokens, start + 1)?;
+            let of = start + 1 + day.len;
+            if !day.ordinal || word(tokens, of).as_deref() != Some("of") {
+                return None;
+            }
+            (month(tokens, of + 1)?, day, of + 2)
+        }
+        None => return None,
+    };
+    if !(1..=31).contains(&day.value) || !day.decimals.is_empty() || day.len > 2 {
+        return None;
+    }
+    let year = year(tokens, end);
+    // "you may one day" is not a date
+    if month == "May" && !day.ordinal && year.is_none() {
+        return None;
+    }
+    let mut written = format!("{month} {}", day.value);
+    if let Some((year, len)) = year {
+        written.push_str(&format!(", {year}"));
+        end += len;
+    }
+    Some((written, end - start))
+}
+
+fn month(tokens: &[&str], i: usize) -> Option<String> {
+    let word = word(tokens, i)?;
+    MONTHS.contains(&word.as_str()).then(|| capitalise(&word))
+}
+
+// "nineteen ninety nine", "twenty oh five", "two thousand and ten"
+fn year(tokens: &[&str], start: usize) -> Option<(u64, usize)> {
+    if let Some(number) = number(tokens, start) {
+        if (1000..3000).contains(&number.value) && number.decimals.is_empty() && !number.ordinal {
+            return Some((number.value, number.len));
+        }
+    }
+    paired_year(tokens, start)
+}
+
+// years read as two numbers, which would otherwise be written as two
+fn paired_year(tokens: &[&str], start: usize) -> Option<(u64, usize)> {
+    let (century, len) = two_digits(tokens, start).filter(|(f, _)| *f >= 10)?;
+    let rest = start + len;
+    match word(tokens, rest).as_deref() {
+        Some("hundred") => Some((century * 100, len + 1)),
+        Some("oh") => {
+            let (Kind::Unit, unit) = lookup(CARDINALS, &word(tokens, rest + 1)?)? else {
+                return None;
+            };
+            Some((century * 100 + unit, len + 2))
+        }
+        _ => {
+            let (decade, decade_len) = two_digits(tokens, rest).filter(|(f, _)| *f >= 10)?;
+            Some((century * 100 + decade, len + decade_len))
+        }
+    }
+}
+
+// a teen, or tens with an optional unit
+fn two_digits(tokens: &[&str], start: usize) -> Option<(u64, usize)> {
+    match lookup(CARDINALS, &word(tokens, start)?)? {
+        (Kind::Teen, value) => Some((value, 1)),
+        (Kind::Tens, value) => match word(tokens, start + 1).and_then(|f| lookup(CARDINALS, &f)) {
+            Some((Kind::Unit, unit)) if unit > 0 => Some((value + unit, 2)),
+            _ => Some((value, 1)),
+        },
+        (Kind::Unit, value) => Some((value, 1)),
+        _ => None,
+    }
+}
+
+// "three thirty pm", "seven o'clock", "at nine oh five"
+fn time(tokens: &[&str], start: usize) -> Option<(String, usize)> {
+    let hour = match lookup(CARDINALS, &word(tokens, start)?)? {
+        (Kind::Unit | Kind::Teen, hour) if (1..=12).contains(&hour) => hour,
+        _ => return None,
+    };
+    let mut end = start + 1;
+    let mut minutes = None;
+    match word(tokens, end).as_deref() {
+        Some("o'clock") => return Some((format!("{hour}:00"), 2)),
+        Some("oh") => {
+            if let Some((Kind::Unit, minute)) =
+                word(tokens, end + 1).and_then(|f| lookup(CARDINALS, &f))
+            {
+                minutes = Some(minute);
+                end += 2;
+            }
+        }
+        _ => {
+            if let Some((minute, len)) = two_digits(tokens, end) {
+                if (10..60).contains(&minute) {
+                    minutes = Some(minute);
+                    end += len;
+                }
+            }
+        }
+    }
+
+    let meridiem = match (
+        word(tokens, end).as_deref(),
+        word(tokens, end + 1).as_deref(),
+    ) {
+        (Some("am" | "a.m."), _) => Some(("am", 1)),
+        (Some("pm" | "p.m."), _) => Some(("pm", 1)),
+        (Some("a"), Some("m")) => Some(("am", 2)),
+        (Some("p"), Some("m")) => Some(("pm", 2)),
+        _ => None,
+    };
+    let after_at = start > 0 && tokens[start - 1].eq_ignore_ascii_case("at");
+    if meridiem.is_none() && !(after_at && minutes.is_some()) {
+        return None;
+    }
+
+    let mut written = match minutes {
+        Some(minutes) => format!("{hour}:{minutes:02}"),
+        None => hour.to_string(),
+    };
+    if let Some((meridiem, len)) = meridiem {
+        written.push_str(&format!(" {meridiem}"));
+        end += len;
+    }
+    Some((written, end - start))
+}
+
+fn grouped(value: u64) -> String {
+    let digits = value.to_string();
+    // four digit numbers read better without a separator
+    if value < 10_000 {
+        return digits;
+    }
+    let reversed: Vec<char> = digits.chars().rev().collect();
+    let groups: Vec<String> = reversed
+        .chunks(3)
+        .rev()
+        .map(|f| f.iter().rev().collect())
+        .collect();
+    groups.join(",")
+}
+
+fn ordinal_suffix(value: u64) -> &'static str {
+    match (value % 10, value % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+
+pub(super) fn capitalise(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
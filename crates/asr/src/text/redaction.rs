@@ -0,0 +1,355 @@
+use std::ops::Range;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{TranscriptionResult, Word};
+
+/// What to mask before a transcript is shown or stored. Nothing is masked by default
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Redaction {
+    /// Masks swearing, keeping the first letter so it still reads as a word
+    #[serde(default)]
+    pub profanity: bool,
+
+    /// Replaces email addresses, phone numbers and card numbers, written or spoken digit by digit
+    #[serde(rename = "personal-data")]
+    #[serde(default)]
+    pub personal_data: bool,
+
+    /// Masked along with the built in profanity list
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<String>,
+
+    /// Regular expressions for anything else, matches become `[redacted]`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
+}
+
+const PROFANITY: &[&str] = &[
+    "arse",
+    "arsehole",
+    "ass",
+    "asshole",
+    "bastard",
+    "bitch",
+    "bollocks",
+    "bullshit",
+    "crap",
+    "cunt",
+    "damn",
+    "dick",
+    "fuck",
+    "fucked",
+    "fucking",
+    "motherfucker",
+    "piss",
+    "pissed",
+    "shit",
+    "shitty",
+    "wanker",
+];
+
+// "oh" is spoken for zero
+const DIGITS: &[(&str, char)] = &[
+    ("zero", '0'),
+    ("oh", '0'),
+    ("one", '1'),
+    ("two", '2'),
+    ("three", '3'),
+    ("four", '4'),
+    ("five", '5'),
+    ("six", '6'),
+    ("seven", '7'),
+    ("eight", '8'),
+    ("nine", '9'),
+];
+
+const EMAIL: &str = "[email]";
+const PHONE_NUMBER: &str = "[phone number]";
+const CARD_NUMBER: &str = "[card number]";
+const REDACTED: &str = "[redacted]";
+
+/// Rules of a [`Redaction`] ready to apply, build it once rather than for every result
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    profanity: Vec<String>,
+    // written forms of personal data, most specific first
+    personal_data: Vec<(Regex, &'static str)>,
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    /// Patterns that are not valid regular expressions are left out with a warning
+    pub fn new(redaction: &Redaction) -> Self {
+        let built_in = if redaction.profanity { PROFANITY } else { &[] };
+        let profanity = built_in
+            .iter()
+            .map(|f| f.to_string())
+            .chain(redaction.words.iter().map(|f| f.to_lowercase()))
+            .collect();
+        let personal_data = if redaction.personal_data {
+            [
+                (r"(?i)\b[\w.+-]+@[\w-]+(?:\.[\w-]+)+\b", EMAIL),
+                (
+                    r"(?i)\b\w+(?: dot \w+)* at \w+(?: dot \w+)* dot (?:com|org|net|io|co|uk|de|edu|gov)\b",
+                    EMAIL,
+                ),
+                (r"\b(?:\d[ -]?){12,18}\d\b", CARD_NUMBER),
+                (r"(?:\+|\b)\d(?:[ ().-]{0,2}\d){6,14}\b", PHONE_NUMBER),
+            ]
+            .into_iter()
+            .map(|(pattern, label)| {
+                (
+                    Regex::new(pattern).expect("built in patterns are valid"),
+                    label,
+                )
+            })
+            .collect()
+        } else {
+            Vec::new()
+        };
+        let patterns = redaction
+            .patterns
+            .iter()
+            .filter_map(|f| match Regex::new(f) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    warn!(pattern = f, "redaction pattern left out: {e}");
+                    None
+                }
+            })
+            .collect();
+        Self {
+            profanity,
+            personal_data,
+            patterns,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profanity.is_empty() && self.personal_data.is_empty() && self.patterns.is_empty()
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        self.redact_spans(text).0
+    }
+
+    /// Redacts the text and alternatives of a result, partial or not. Words that overlap
+    /// anything that was replaced are masked too
+    pub fn apply(&self, mut result: TranscriptionResult) -> TranscriptionResult {
+        if self.is_empty() {
+            return result;
+        }
+        let (text, replaced) = self.redact_spans(&result.text);
+        self.mask_words(&mut result.words, &result.text, &replaced);
+        result.text = text;
+        for alternative in result.alternatives.iter_mut() {
+            let (text, replaced) = self.redact_spans(&alternative.text);
+            self.mask_words(&mut alternative.words, &alternative.text, &replaced);
+            alternative.text = text;
+        }
+        result
+    }
+
+    // the redacted text along with the byte ranges of `text` that were replaced
+    fn redact_spans(&self, text: &str) -> (String, Vec<Range<usize>>) {
+        if self.is_empty() {
+            return (text.to_owned(), Vec::new());
+        }
+        let (mut redacted, replaced) = self.replace(text);
+        if !self.profanity.is_empty() {
+            redacted = redacted
+                .split_whitespace()
+                .map(|f| self.mask_profanity(f))
+                .collect::<Vec<_>>()
+                .join(" ");
+        }
+        (redacted, replaced)
+    }
+
+    // personal data and patterns are looked for in one go, the first rule to claim a part of
+    // the text replaces it
+    fn replace(&self, text: &str) -> (String, Vec<Range<usize>>) {
+        let (written, pieces) = if self.personal_data.is_empty() {
+            let whole = Piece {
+                written: 0..text.len(),
+                spoken: 0..text.len(),
+                exact: true,
+            };
+            (text.to_owned(), vec![whole])
+        } else {
+            spoken_numbers(text)
+        };
+
+        let rules = self
+            .personal_data
+            .iter()
+            .map(|(regex, label)| (regex, *label))
+            .chain(self.patterns.iter().map(|f| (f, REDACTED)));
+        let mut matches: Vec<(Range<usize>, &str)> = Vec::new();
+        for (regex, label) in rules {
+            for found in regex.find_iter(&written) {
+                let range = found.range();
+                if !matches.iter().any(|(f, _)| overlaps(f, &range)) {
+                    matches.push((range, label));
+                }
+            }
+        }
+        matches.sort_by_key(|(f, _)| f.start);
+
+        let mut redacted = String::with_capacity(written.len());
+        let mut end = 0;
+        for (range, label) in &matches {
+            redacted.push_str(&written[end..range.start]);
+            redacted.push_str(label);
+            end = range.end;
+        }
+        redacted.push_str(&written[end..]);
+        let replaced = matches
+            .iter()
+            .filter_map(|(range, _)| spoken_range(&pieces, range))
+            .collect();
+        (redacted, replaced)
+    }
+
+    // words are found in the text in order, anything that cannot be placed is masked as soon
+    // as something was replaced since it could have been part of it
+    fn mask_words(&self, words: &mut [Word], text: &str, replaced: &[Range<usize>]) {
+        let tokens = tokens(text);
+        let mut next = 0;
+        for word in words.iter_mut() {
+            let bare_word = bare(&word.word).to_lowercase();
+            let found = tokens[next..]
+                .iter()
+                .position(|(_, f)| bare(f).to_lowercase() == bare_word);
+            let hidden = match found {
+                Some(i) => {
+                    let range = &tokens[next + i].0;
+                    next += i + 1;
+                    replaced.iter().any(|f| overlaps(f, range))
+                }
+                None => !replaced.is_empty(),
+            };
+            word.word = if hidden {
+                REDACTED.to_owned()
+            } else {
+                self.mask_profanity(&word.word)
+            };
+        }
+    }
+
+    fn mask_profanity(&self, word: &str) -> String {
+        let bare = bare(word);
+        if bare.is_empty() || !self.profanity.iter().any(|f| f.eq_ignore_ascii_case(bare)) {
+            return word.to_owned();
+        }
+        let mut chars = bare.chars();
+        let first = chars.next().unwrap_or_default();
+        let masked: String = std::iter::once(first).chain(chars.map(|_| '*')).collect();
+        word.replacen(bare, &masked, 1)
+    }
+}
+
+/// Part of the text personal data is looked for in, and where it came from in what was said
+struct Piece {
+    written: Range<usize>,
+    spoken: Range<usize>,
+    // written as it was spoken, so positions inside it carry over
+    exact: bool,
+}
+
+// long runs of spoken digits become figures so the written patterns catch them
+fn spoken_numbers(text: &str) -> (String, Vec<Piece>) {
+    let words = tokens(text);
+    let mut written = String::with_capacity(text.len());
+    let mut pieces = Vec::with_capacity(words.len());
+    let mut i = 0;
+    while i < words.len() {
+        if !written.is_empty() {
+            written.push(' ');
+        }
+        let start = written.len();
+        let run = words[i..]
+            .iter()
+            .take_while(|(_, f)| digit(f).is_some())
+            .count();
+        let (spoken, exact) = if run >= 7 {
+            written.extend(words[i..i + run].iter().filter_map(|(_, f)| digit(f)));
+            let spoken = words[i].0.start..words[i + run - 1].0.end;
+            i += run;
+            (spoken, false)
+        } else {
+            let (spoken, word) = &words[i];
+            written.push_str(word);
+            i += 1;
+            (spoken.clone(), true)
+        };
+        pieces.push(Piece {
+            written: start..written.len(),
+            spoken,
+            exact,
+        });
+    }
+    (written, pieces)
+}
+
+// where a match in the written text was said, a run of spoken digits counts as a whole
+fn spoken_range(pieces: &[Piece], range: &Range<usize>) -> Option<Range<usize>> {
+    let mut touched = pieces.iter().filter(|f| overlaps(&f.written, range));
+    let first = touched.next()?;
+    let last = touched.next_back().unwrap_or(first);
+    let start = if first.exact {
+        first.spoken.start + range.start.saturating_sub(first.written.start)
+    } else {
+        first.spoken.start
+    };
+    let end = if last.exact {
+        last.spoken.start + range.end.min(last.written.end) - last.written.start
+    } else {
+        last.spoken.end
+    };
+    Some(start..end)
+}
+
+// words of the text along with where they are in it
+fn tokens(text: &str) -> Vec<(Range<usize>, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                tokens.push((s..i, &text[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+fn bare(word: &str) -> &str {
+    word.trim_matches(|f: char| !f.is_alphanumeric() && f != '\'')
+}
+
+fn digit(word: &str) -> Option<char> {
+    let mut chars = word.chars();
+    match (chars.next(), chars.next()) {
+        (Some(digit), None) if digit.is_ascii_digit() => Some(digit),
+        _ => DIGITS
+            .iter()
+            .find(|(f, _)| f.eq_ignore_ascii_case(word))
+            .map(|(_, digit)| *digit),
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Alternative, TranscriptionResult, Word};
+
+/// Words and phrases a general model gets wrong, such as names of people, projects and services
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Vocabulary {
+    /// Phrases to prefer when a backend offers alternatives
+    #[serde(default)]
+    pub phrases: Vec<Phrase>,
+    /// Rewrites for what a backend consistently hears instead of a word it does not know
+    #[serde(default)]
+    pub replacements: Vec<Replacement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Phrase {
+    pub phrase: String,
+    /// A hypothesis containing the phrase overtakes those ranked fewer than this many places
+    /// above it
+    #[serde(default = "default_boost")]
+    pub boost: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Replacement {
+    pub from: String,
+    pub to: String,
+}
+
+fn default_boost() -> f32 {
+    1.5
+}
+
+impl Vocabulary {
+    pub fn is_empty(&self) -> bool {
+        self.phrases.is_empty() && self.replacements.is_empty()
+    }
+
+    /// Every phrase in lower case, as grammars and phrase lists take them
+    pub fn phrase_list(&self) -> impl Iterator<Item = String> + '_ {
+        self.phrases.iter().map(|f| f.phrase.to_lowercase())
+    }
+
+    /// Reorders hypotheses, best first, so boosted phrases move up. Ties keep the backend's order
+    pub fn rescore(&self, alternatives: &mut Vec<Alternative>) {
+        if self.phrases.is_empty() || alternatives.len() < 2 {
+            return;
+        }
+        let mut scored: Vec<_> = alternatives
+            .drain(..)
+            .enumerate()
+            .map(|(rank, f)| (self.boost(&f.text) - rank as f32, f))
+            .collect();
+        // stable, so equal scores stay in rank order
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        alternatives.extend(scored.into_iter().map(|(_, f)| f));
+    }
+
+    /// Applies the replacement rules to the text, words and alternatives of a result
+    pub fn replace(&self, mut result: TranscriptionResult) -> TranscriptionResult {
+        if self.replacements.is_empty() {
+            return result;
+        }
+        result.text = self.replace_text(&result.text);
+        result.words = self.replace_words(result.words);
+        for alternative in result.alternatives.iter_mut() {
+            alternative.text = self.replace_text(&alternative.text);
+            alternative.words = self.replace_words(std::mem::take(&mut alternative.words));
+        }
+        result
+    }
+
+    fn boost(&self, text: &str) -> f32 {
+        let words: Vec<_> = text.split_whitespace().collect();
+        self.phrases
+            .iter()
+            .filter(|f| {
+                let phrase: Vec<_> = f.phrase.split_whitespace().collect();
+                find(&words, &phrase, 0).is_some()
+            })
+            .map(|f| f.boost)
+            .sum()
+    }
+
+    fn replace_text(&self, text: &str) -> String {
+        let mut words: Vec<String> = text.split_whitespace().map(str::to_owned).collect();
+        for replacement in &self.replacements {
+            let from: Vec<_> = replacement.from.split_whitespace().collect();
+            let mut start = 0;
+            while let Some(i) = find(&words, &from, start) {
+                words.splice(i..i + from.len(), [replacement.to.clone()]);
+                start = i + 1;
+            }
+        }
+        words.join(" ")
+    }
+
+    // the replacement spans the time of the words it stands for and is as sure as the least
+    // certain of them
+    fn replace_words(&self, mut words: Vec<Word>) -> Vec<Word> {
+        for replacement in &self.replacements {
+            let from: Vec<_> = replacement.from.split_whitespace().collect();
+            let mut start = 0;
+            loop {
+                let text: Vec<_> = words.iter().map(|f| f.word.as_str()).collect();
+                let Some(i) = find(&text, &from, start) else {
+                    break;
+                };
+                let matched = &words[i..i + from.len()];
+                let confidence = matched
+                    .iter()
+                    .map(|f| f.confidence)
+                    .reduce(|a, b| match (a, b) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        _ => None,
+                    })
+                    .flatten();
+                let word = Word::new(
+                    &replacement.to,
+                    matched[0].start,
+                    matched[matched.len() - 1].end,
+                    confidence,
+                );
+                words.splice(i..i + from.len(), [word]);
+                start = i + 1;
+            }
+        }
+        words
+    }
+}
+
+// position of the first case insensitive whole word match of `needle` at or after `start`
+fn find<T: AsRef<str>>(haystack: &[T], needle: &[&str], start: usize) -> Option<usize> {
+    if needle.is_empty() || haystack.len() < needle.len() {
+        return None;
+    }
+    (start..=haystack.len() - needle.len()).find(|&i| {
+        needle
+            .iter()
+            .zip(&haystack[i..])
+            .all(|(a, b)| a.eq_ignore_ascii_case(b.as_ref()))
+    })
+}
@@ -0,0 +1,168 @@
+use std::{sync::Arc, time::Duration};
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::split_channels;
+
+// at least this much audio per frame, rounded up to a power of two for the fft
+const FRAME: Duration = Duration::from_millis(32);
+
+// attenuation at full strength, deeper than this and what is left of the noise starts to warble
+const MAX_ATTENUATION_DB: f32 = 25.0;
+
+// how much of the previous frame's speech estimate carries into the next, smoothing the gains
+const DECISION_DIRECTED: f32 = 0.98;
+
+// per frame, how much of the noise estimate is kept in bins that sound like noise, and in bins
+// that sound like speech, where it only creeps up so a new noise is eventually learnt
+const NOISE_SMOOTHING: f32 = 0.95;
+const SPEECH_SMOOTHING: f32 = 0.999;
+
+// bins this many times louder than the noise estimate are taken to have speech in them
+const SPEECH_SNR: f32 = 4.0;
+
+/// Removes steady background noise such as fans and hum from interleaved audio. Every channel
+/// keeps its own estimate of the noise, which adapts as the room changes
+pub struct NoiseSuppressor {
+    frame: usize,
+    window: Vec<f32>,
+    forward: Arc<dyn Fft<f32>>,
+    inverse: Arc<dyn Fft<f32>>,
+    channels: Vec<Channel>,
+    floor: f32,
+}
+
+struct Channel {
+    // input not yet processed, starting at the next frame
+    pending: Vec<f32>,
+    // second half of the last frame, waiting for the next one to overlap it
+    overlap: Vec<f32>,
+    // power of the noise in each bin, none until the first frame
+    noise: Option<Vec<f32>>,
+    // from the last frame, for the decision directed estimate
+    gains: Vec<f32>,
+    snr: Vec<f32>,
+}
+
+impl NoiseSuppressor {
+    /// `strength` goes from 0, which leaves the audio as it is, to 1
+    pub fn new(sample_rate: f32, channels: u16, strength: f32) -> Self {
+        let frame = ((sample_rate * FRAME.as_secs_f32()) as usize)
+            .next_power_of_two()
+            .max(16);
+        let hop = frame / 2;
+        // square root of a periodic hann window, so analysis and synthesis together add up to one
+        let window = (0..frame)
+            .map(|n| {
+                (0.5 * (1.0 - (2.0 * std::f32::consts::PI * n as f32 / frame as f32).cos())).sqrt()
+            })
+            .collect();
+        let mut planner = FftPlanner::new();
+        let bins = frame / 2 + 1;
+        let mut suppressor = Self {
+            frame,
+            window,
+            forward: planner.plan_fft_forward(frame),
+            inverse: planner.plan_fft_inverse(frame),
+            channels: (0..channels.max(1))
+                .map(|_| Channel {
+                    // lines the first frame up so output starts half a frame behind the input
+                    pending: vec![0.0; hop],
+                    overlap: vec![0.0; hop],
+                    noise: None,
+                    gains: vec![1.0; bins],
+                    snr: vec![1.0; bins],
+                })
+                .collect(),
+            floor: 1.0,
+        };
+        suppressor.set_strength(strength);
+        suppressor
+    }
+
+    /// Takes effect from the next frame, what has been learnt of the noise is kept
+    pub fn set_strength(&mut self, strength: f32) {
+        let strength = strength.clamp(0.0, 1.0);
+        self.floor = 10f32.powf(-MAX_ATTENUATION_DB * strength / 20.0);
+    }
+
+    /// Cleans the next chunk of interleaved samples, which has to hold whole frames of every
+    /// channel. Output comes half an fft frame behind and in whole fft frames, so it is not
+    /// always as long as the chunk
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        let count = self.channels.len();
+        let split = split_channels(input, count as u16);
+        let mut outputs = Vec::with_capacity(count);
+        for (i, samples) in split.into_iter().enumerate() {
+            self.channels[i].pending.extend(samples);
+            outputs.push(self.process_channel(i));
+        }
+        let length = outputs.iter().map(Vec::len).min().unwrap_or_default();
+        (0..length)
+            .flat_map(|n| outputs.iter().map(move |f| f[n]))
+            .collect()
+    }
+
+    fn process_channel(&mut self, index: usize) -> Vec<f32> {
+        let frame = self.frame;
+        let hop = frame / 2;
+        let bins = frame / 2 + 1;
+        let mut output = Vec::new();
+        while self.channels[index].pending.len() >= frame {
+            let channel = &mut self.channels[index];
+            let mut spectrum: Vec<Complex<f32>> = channel.pending[..frame]
+                .iter()
+                .zip(&self.window)
+                .map(|(sample, window)| Complex {
+                    re: sample * window,
+                    im: 0.0,
+                })
+                .collect();
+            self.forward.process(&mut spectrum);
+
+            let power: Vec<f32> = spectrum[..bins].iter().map(|f| f.norm_sqr()).collect();
+            let noise = channel.noise.get_or_insert_with(|| power.clone());
+            for bin in 0..bins {
+                let snr = power[bin] / noise[bin].max(f32::MIN_POSITIVE);
+                // wiener gain from an estimate of the speech to noise ratio that leans on the
+                // previous frame, which keeps musical noise down
+                let prior = DECISION_DIRECTED * channel.gains[bin].powi(2) * channel.snr[bin]
+                    + (1.0 - DECISION_DIRECTED) * (snr - 1.0).max(0.0);
+                let gain = (prior / (1.0 + prior)).max(self.floor);
+                channel.gains[bin] = gain;
+                channel.snr[bin] = snr;
+
+                let rate = if snr < SPEECH_SNR {
+                    NOISE_SMOOTHING
+                } else {
+                    SPEECH_SMOOTHING
+                };
+                noise[bin] = rate * noise[bin] + (1.0 - rate) * power[bin];
+
+                spectrum[bin] *= gain;
+                // the upper half mirrors the lower one for real input
+                if bin > 0 && bin < frame - bin {
+                    spectrum[frame - bin] *= gain;
+                }
+            }
+
+            self.inverse.process(&mut spectrum);
+            let scale = 1.0 / frame as f32;
+            let frame_out: Vec<f32> = spectrum
+                .iter()
+                .zip(&self.window)
+                .map(|(f, window)| f.re * scale * window)
+                .collect();
+            output.extend(
+                channel
+                    .overlap
+                    .iter()
+                    .zip(&frame_out[..hop])
+                    .map(|(a, b)| a + b),
+            );
+            channel.overlap.copy_from_slice(&frame_out[hop..]);
+            channel.pending.drain(..hop);
+        }
+        output
+    }
+}
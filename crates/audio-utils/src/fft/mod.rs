@@ -1,10 +1,103 @@
-use std::ops::Range;
+use std::{ops::Range, sync::Arc};
 
-use rustfft::{num_complex::Complex, FftPlanner};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
 
-use crate::window::hann_window;
+use crate::window::hann_coefficients;
 
-/// puts buffer into FFT alogrithm and applies filters and modifiers to it
+/// How a frame is shaped into the spectrum that gets drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectrumSettings {
+    pub max_frequency: u16,
+    pub volume: f32,
+    pub frequency_scale_range: Range<u16>,
+    pub smoothing_amount: u8,
+    pub smoothing_size: u8,
+    pub frequency_scale_amount: u8,
+}
+
+/// Turns frames of a fixed size into spectra, keeping the fft plan, window and buffers between
+/// frames so a stream of them is processed without allocating
+pub struct SpectrumAnalyser {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    magnitudes: Vec<f32>,
+    output: Vec<f32>,
+    // what the output is swapped with while frequencies are scaled
+    scaled: Vec<f32>,
+}
+
+impl SpectrumAnalyser {
+    pub fn new(size: usize) -> Self {
+        let fft = FftPlanner::new().plan_fft_forward(size);
+        // at most a quarter of the bins is kept, then up to doubled when frequencies are scaled
+        let bins = size / 4;
+        Self {
+            window: hann_coefficients(size),
+            spectrum: vec![Complex::default(); size],
+            scratch: vec![Complex::default(); fft.get_inplace_scratch_len()],
+            magnitudes: Vec::with_capacity(bins),
+            output: Vec::with_capacity(bins * 2),
+            scaled: Vec::with_capacity(bins * 2),
+            fft,
+        }
+    }
+
+    /// Samples per frame
+    pub fn size(&self) -> usize {
+        self.window.len()
+    }
+
+    /// The spectrum of a frame of exactly `size` samples, overwritten by the next call
+    pub fn process(&mut self, frame: &[f32], settings: &SpectrumSettings) -> &[f32] {
+        assert_eq!(
+            frame.len(),
+            self.size(),
+            "frame does not match the analyser"
+        );
+        for ((bin, sample), multiplier) in self.spectrum.iter_mut().zip(frame).zip(&self.window) {
+            *bin = Complex {
+                re: multiplier * sample,
+                im: 0.0,
+            };
+        }
+        self.fft
+            .process_with_scratch(&mut self.spectrum, &mut self.scratch);
+
+        // remove mirroring
+        let bins = (self.spectrum.len() as f32 * 0.25) as usize;
+        // max frequency
+        let percentage: f32 = settings.max_frequency as f32 / 22050.0;
+        let bins = (bins as f32 * percentage) as usize;
+        self.magnitudes.clear();
+        self.magnitudes
+            .extend(self.spectrum[..bins].iter().map(|f| f.norm()));
+
+        normalize(&self.magnitudes, &mut self.output, settings.volume);
+
+        scale_frequencies(
+            &mut self.output,
+            &mut self.scaled,
+            &settings.frequency_scale_range,
+            settings.frequency_scale_amount,
+            settings.max_frequency,
+        );
+
+        smooth(
+            &mut self.output,
+            settings.smoothing_amount,
+            settings.smoothing_size,
+        );
+
+        //    bar_reduction(&mut output_buffer, config.density_reduction);
+
+        &self.output
+    }
+}
+
+/// puts buffer into FFT alogrithm and applies filters and modifiers to it. Plans a new FFT each
+/// call, a `SpectrumAnalyser` should be kept around for a stream of buffers
 pub fn convert_buffer(
     input_buffer: &[f32],
     max_frequency: u16,
@@ -14,56 +107,28 @@ pub fn convert_buffer(
     smoothing_size: u8,
     frequency_scale_amount: u8,
 ) -> Vec<f32> {
-    let input_buffer = apodize(input_buffer);
-
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(input_buffer.len());
-
-    let mut buffer: Vec<Complex<f32>> = input_buffer
-        .iter()
-        .map(|f| Complex { re: *f, im: 0.0 })
-        .collect();
-
-    fft.process(&mut buffer[..]);
-
-    let output_buffer: Vec<f32> = buffer.iter().map(|f| f.norm()).collect();
-
-    // remove mirroring
-    let output_buffer = output_buffer[0..(output_buffer.len() as f32 * 0.25) as usize].to_vec();
-
-    // max frequency
-    let percentage: f32 = max_frequency as f32 / 22050.0;
-    let output_buffer =
-        output_buffer[0..(output_buffer.len() as f32 * percentage) as usize].to_vec();
-
-    let mut output_buffer = normalize(output_buffer, volume);
-
-    scale_frequencies(
-        &mut output_buffer,
-        frequency_scale_range,
-        frequency_scale_amount,
+    let settings = SpectrumSettings {
         max_frequency,
-    );
-
-    smooth(&mut output_buffer, smoothing_amount, smoothing_size);
-
-    //    bar_reduction(&mut output_buffer, config.density_reduction);
-
-    output_buffer
-}
-
-fn apodize(buffer: &[f32]) -> Vec<f32> {
-    //  let window = apodize::hanning_iter(buffer.len()).collect::<Vec<f64>>();
-
-    //  buffer
-    //      .iter()
-    //      .enumerate()
-    //      .map(|(idx, val)| window[idx] as f32 * val)
-    //      .collect()
-    hann_window(buffer)
+        volume,
+        frequency_scale_range: frequency_scale_range.clone(),
+        smoothing_amount,
+        smoothing_size,
+        frequency_scale_amount,
+    };
+    SpectrumAnalyser::new(input_buffer.len())
+        .process(input_buffer, &settings)
+        .to_vec()
 }
 
-fn scale_frequencies(buffer: &mut Vec<f32>, fav_freqs: &Range<u16>, doubling: u8, max_freqs: u16) {
+// every value from the start of the range gets the average of it and the next one put after it,
+// `scratch` is left with what was there before
+fn scale_frequencies(
+    buffer: &mut Vec<f32>,
+    scratch: &mut Vec<f32>,
+    fav_freqs: &Range<u16>,
+    doubling: u8,
+    max_freqs: u16,
+) {
     let mut doubled: usize = 0;
     let buffer_len = buffer.len();
     for _ in 0..doubling {
@@ -78,35 +143,40 @@ fn scale_frequencies(buffer: &mut Vec<f32>, fav_freqs: &Range<u16>, doubling: u8
         let normalized_end_pos: usize =
             ((buffer_len as f32 / end_pos).sqrt() * end_pos) as usize + doubled;
 
-        let mut position: usize = normalized_start_pos;
-        for _ in normalized_start_pos..normalized_end_pos {
-            if position < buffer.len() - 1 {
-                let value: f32 = (buffer[position] + buffer[position + 1]) / 2.0;
-
-                buffer.insert(position + 1, value);
-                position += 2;
-                doubled += 1;
-            }
+        // stops short of the last value, which has nothing after it
+        let inserted = normalized_end_pos
+            .saturating_sub(normalized_start_pos)
+            .min(buffer.len().saturating_sub(normalized_start_pos + 1));
+        if inserted == 0 {
+            continue;
+        }
+        let end = normalized_start_pos + inserted;
+        scratch.clear();
+        scratch.extend_from_slice(&buffer[..normalized_start_pos]);
+        for pair in buffer[normalized_start_pos..=end].windows(2) {
+            scratch.push(pair[0]);
+            scratch.push((pair[0] + pair[1]) / 2.0);
         }
+        scratch.extend_from_slice(&buffer[end..]);
+        std::mem::swap(buffer, scratch);
+        doubled += inserted;
     }
 }
 
-fn normalize(buffer: Vec<f32>, volume: f32) -> Vec<f32> {
+fn normalize(buffer: &[f32], output_buffer: &mut Vec<f32>, volume: f32) {
     let buffer_len: usize = buffer.len();
-    let mut output_buffer: Vec<f32> = vec![0.0; buffer_len];
+    output_buffer.clear();
+    output_buffer.resize(buffer_len, 0.0);
 
     let mut start_pos: usize;
     let mut end_pos: usize = 0;
 
-    let mut pos_index: Vec<[usize; 2]> = Vec::with_capacity(buffer_len);
-
     for (i, item) in buffer.iter().enumerate().take(buffer_len) {
         let offset: f32 = (buffer_len as f32 / (i + 1) as f32).sqrt();
         // sets positions needed for future operations
         let pos: usize = (i as f32 * offset) as usize;
         start_pos = end_pos;
         end_pos = pos;
-        pos_index.push([start_pos, end_pos]);
 
         // volume normalisation
         //let y = buffer[i] / offset.powi(2) * volume /* old and non linear method */
@@ -127,11 +197,9 @@ fn normalize(buffer: Vec<f32>, volume: f32) -> Vec<f32> {
             output_buffer[s_p] = y;
         }
     }
-
-    output_buffer
 }
 
-fn smooth(buffer: &mut Vec<f32>, smoothing: u8, smoothing_size: u8) {
+fn smooth(buffer: &mut [f32], smoothing: u8, smoothing_size: u8) {
     if buffer.len() <= smoothing_size.into() || smoothing_size == 0 {
         return;
     }
@@ -158,8 +226,16 @@ fn smooth(buffer: &mut Vec<f32>, smoothing: u8, smoothing_size: u8) {
 pub fn merge_buffers(
     buffer: &[Vec<f32>], // EVERY 1D buffer of whole buffer MUST have the same length
 ) -> Vec<f32> {
+    let mut output_buffer = Vec::new();
+    merge_buffers_into(buffer, &mut output_buffer);
+    output_buffer
+}
+
+/// `merge_buffers` into a buffer that is kept around, so nothing is allocated once it has grown
+pub fn merge_buffers_into(buffer: &[Vec<f32>], output_buffer: &mut Vec<f32>) {
     let mut smoothed_percentage: f32 = 0.0;
-    let mut output_buffer: Vec<f32> = vec![0.0; buffer[0].len()];
+    output_buffer.clear();
+    output_buffer.resize(buffer[0].len(), 0.0);
     for (pos_z, z_buffer) in buffer.iter().enumerate() {
         // needed for weighting the Importance of earch z_buffer, more frequent -> more important
         // should decrease latency and increase overall responsiveness
@@ -175,6 +251,17 @@ pub fn merge_buffers(
     for b in output_buffer.iter_mut() {
         *b /= smoothed_percentage;
     }
+}
 
-    output_buffer
+/// Same as `merge_buffers(&[merged, latest])`, done in place. An empty `merged` takes `latest`
+/// as it is
+pub fn merge_latest(merged: &mut Vec<f32>, latest: &[f32]) {
+    if merged.is_empty() {
+        merged.extend_from_slice(latest);
+        return;
+    }
+    for (pos_x, value) in merged.iter_mut().enumerate() {
+        let latest = latest.get(pos_x).copied().unwrap_or_default();
+        *value = (*value * 0.5 + latest) / 1.5;
+    }
 }
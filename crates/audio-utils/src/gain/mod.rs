@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+// peaks are held below this, in dBFS
+const CEILING: f32 = -1.0;
+
+// quieter than this, in dBFS, the gain is left where it is so silence is not turned up
+const GATE: f32 = -55.0;
+
+// how long the limiter takes to let go after a peak
+const LIMITER_RELEASE: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AgcSettings {
+    /// Level the input is brought to, in dBFS
+    pub target: f32,
+    /// How quickly the gain comes down when the input gets louder
+    pub attack: Duration,
+    /// How quickly the gain goes back up when it gets quieter
+    pub release: Duration,
+    /// The most the input is ever turned up by, in decibels
+    pub max_gain: f32,
+}
+
+impl Default for AgcSettings {
+    fn default() -> Self {
+        Self {
+            target: -20.0,
+            attack: Duration::from_millis(20),
+            release: Duration::from_millis(1000),
+            max_gain: 30.0,
+        }
+    }
+}
+
+/// Brings interleaved audio to an even level, then limits whatever peaks are left so nothing
+/// clips. Every channel gets the same gain
+pub struct AutomaticGainControl {
+    settings: AgcSettings,
+    channels: usize,
+    // smoothing coefficients per frame of samples
+    attack: f32,
+    release: f32,
+    limiter_release: f32,
+    // mean square level, none until the first frame
+    envelope: Option<f32>,
+    // in decibels
+    gain: f32,
+    limiter: f32,
+}
+
+impl AutomaticGainControl {
+    pub fn new(sample_rate: f32, channels: u16, settings: AgcSettings) -> Self {
+        let coefficient =
+            |f: Duration| 1.0 - (-1.0 / (f.as_secs_f32() * sample_rate).max(1.0)).exp();
+        Self {
+            settings,
+            channels: channels.max(1).into(),
+            attack: coefficient(settings.attack),
+            release: coefficient(settings.release),
+            limiter_release: coefficient(LIMITER_RELEASE),
+            envelope: None,
+            gain: 0.0,
+            limiter: 1.0,
+        }
+    }
+
+    /// Current gain in decibels, leaving out the limiter
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Applies the gain in place. Samples are full scale at 1
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let ceiling = db_to_amplitude(CEILING);
+        for frame in samples.chunks_mut(self.channels) {
+            let power = frame.iter().map(|f| f * f).sum::<f32>() / frame.len() as f32;
+            let envelope = self.envelope.get_or_insert(power);
+            // follows the level both ways at the attack rate, leaning either way would bias it
+            *envelope += (power - *envelope) * self.attack;
+
+            let level = 10.0 * (*envelope + 1e-12).log10();
+            if level > GATE {
+                let wanted = (self.settings.target - level).min(self.settings.max_gain);
+                // turning down is as quick as the attack, turning up as slow as the release
+                let rate = if wanted < self.gain {
+                    self.attack
+                } else {
+                    self.release
+                };
+                self.gain += (wanted - self.gain) * rate;
+            }
+
+            let gain = db_to_amplitude(self.gain);
+            let peak = frame.iter().fold(0.0f32, |peak, f| peak.max(f.abs())) * gain;
+            let limit = if peak > ceiling { ceiling / peak } else { 1.0 };
+            self.limiter = if limit < self.limiter {
+                limit
+            } else {
+                self.limiter + (limit - self.limiter) * self.limiter_release
+            };
+            for sample in frame.iter_mut() {
+                *sample *= gain * self.limiter;
+            }
+        }
+    }
+
+    /// Forgets the level so far, the gain starts from nothing again
+    pub fn reset(&mut self) {
+        self.envelope = None;
+        self.gain = 0.0;
+        self.limiter = 1.0;
+    }
+}
+
+fn db_to_amplitude(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
@@ -1,6 +1,13 @@
+pub mod denoise;
 pub mod fft;
+pub mod gain;
+pub mod resample;
+pub mod vad;
 pub mod window;
 
+#[cfg(test)]
+mod tests;
+
 use dasp::{sample::ToSample, Sample};
 
 pub fn convert_to_mono(input_data: &[i16], channels: u16) -> Vec<i16> {
@@ -0,0 +1,161 @@
+use std::f32::consts::PI;
+
+// zero crossings of the sinc on each side of its centre, more gives a sharper cutoff
+const ZERO_CROSSINGS: usize = 16;
+
+// where the cutoff sits below the lower nyquist frequency, leaving room for the transition band
+const ROLLOFF: f32 = 0.94;
+
+/// Converts a stream from one sample rate to another with a polyphase windowed sinc filter.
+/// Audio can be handed over in chunks of any size, the filter carries on from one to the next
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    // the ratio of the rates as a reduced fraction
+    up: usize,
+    down: usize,
+    // one filter for every position an output sample can fall at between two input samples
+    filters: Vec<Vec<f32>>,
+    // input not yet fully used, starting `half - 1` samples before the next output sample
+    buffer: Vec<f32>,
+    // where the next output sample falls in the buffer, in steps of 1/up of an input sample
+    position: usize,
+    half: usize,
+}
+
+impl Resampler {
+    /// Rates are rounded to whole hertz
+    pub fn new(input_rate: f32, output_rate: f32) -> Self {
+        let input_rate = input_rate.round().max(1.0) as u32;
+        let output_rate = output_rate.round().max(1.0) as u32;
+        let divisor = gcd(input_rate, output_rate);
+        let up = (output_rate / divisor) as usize;
+        let down = (input_rate / divisor) as usize;
+
+        // as a fraction of the input nyquist frequency, lowered when downsampling so nothing
+        // above the output nyquist frequency folds back
+        let cutoff = ROLLOFF * (up as f32 / down as f32).min(1.0);
+        let half = (ZERO_CROSSINGS as f32 / cutoff).ceil() as usize;
+        let filters = (0..up)
+            .map(|phase| {
+                let offset = phase as f32 / up as f32;
+                let mut filter: Vec<f32> = (0..2 * half)
+                    .map(|tap| {
+                        // how far this input sample is from the output sample, in input samples
+                        let distance = tap as f32 - (half - 1) as f32 - offset;
+                        sinc(cutoff * distance) * blackman(distance / half as f32)
+                    })
+                    .collect();
+                // unity gain at dc for every phase, otherwise the phases ripple against each other
+                let gain: f32 = filter.iter().sum();
+                filter.iter_mut().for_each(|f| *f /= gain);
+                filter
+            })
+            .collect();
+
+        Self {
+            input_rate,
+            output_rate,
+            up,
+            down,
+            filters,
+            buffer: vec![0.0; half - 1],
+            position: 0,
+            half,
+        }
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// Resamples the next chunk of the stream. The output lags the input by the length of the
+    /// filter, what is held back comes out with the next chunk
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.up == self.down {
+            return input.to_vec();
+        }
+        self.buffer.extend_from_slice(input);
+        let taps = 2 * self.half;
+        let mut output =
+            Vec::with_capacity(input.len() * self.up / self.down + self.up / self.down + 1);
+        loop {
+            let start = self.position / self.up;
+            if start + taps > self.buffer.len() {
+                break;
+            }
+            let filter = &self.filters[self.position % self.up];
+            output.push(
+                self.buffer[start..start + taps]
+                    .iter()
+                    .zip(filter)
+                    .map(|(sample, coefficient)| sample * coefficient)
+                    .sum(),
+            );
+            self.position += self.down;
+        }
+        // keep only what later output samples still need
+        let used = (self.position / self.up).min(self.buffer.len());
+        self.buffer.drain(..used);
+        self.position -= used * self.up;
+        output
+    }
+
+    /// [`Resampler::process`] for 16 bit samples, clipping anything the filter overshoots
+    pub fn process_i16(&mut self, input: &[i16]) -> Vec<i16> {
+        let input: Vec<f32> = input.iter().map(|f| f32::from(*f)).collect();
+        to_i16(self.process(&input))
+    }
+
+    /// Ends the stream, returning what the filter held back as if silence followed, and starts
+    /// over. It can run a few samples past where the stream ended
+    pub fn flush(&mut self) -> Vec<f32> {
+        let output = self.process(&vec![0.0; self.half]);
+        self.reset();
+        output
+    }
+
+    /// [`Resampler::flush`] for 16 bit samples
+    pub fn flush_i16(&mut self) -> Vec<i16> {
+        to_i16(self.flush())
+    }
+
+    /// Forgets the stream so far, the next chunk starts from silence
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.buffer.resize(self.half - 1, 0.0);
+        self.position = 0;
+    }
+}
+
+fn to_i16(samples: Vec<f32>) -> Vec<i16> {
+    samples
+        .into_iter()
+        .map(|f| f.round().clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16)
+        .collect()
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < f32::EPSILON {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// between -1 and 1, zero at both ends
+fn blackman(x: f32) -> f32 {
+    0.42 + 0.5 * (PI * x).cos() + 0.08 * (2.0 * PI * x).cos()
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
@@ -0,0 +1,77 @@
+use std::f32::consts::PI;
+
+use crate::denoise::NoiseSuppressor;
+
+const SAMPLE_RATE: f32 = 16_000.0;
+
+fn noise(samples: usize, amplitude: f32) -> Vec<f32> {
+    let mut state: u32 = 0x8765_4321;
+    (0..samples)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            ((state >> 8) as f32 / 8_388_608.0 - 1.0) * amplitude
+        })
+        .collect()
+}
+
+fn tone(samples: usize, amplitude: f32) -> Vec<f32> {
+    (0..samples)
+        .map(|i| (2.0 * PI * 440.0 * i as f32 / SAMPLE_RATE).sin() * amplitude)
+        .collect()
+}
+
+fn level(samples: &[f32]) -> f32 {
+    let power = samples.iter().map(|f| f * f).sum::<f32>() / samples.len() as f32;
+    10.0 * power.log10()
+}
+
+fn process(suppressor: &mut NoiseSuppressor, input: &[f32]) -> Vec<f32> {
+    input
+        .chunks(440)
+        .flat_map(|f| suppressor.process(f))
+        .collect()
+}
+
+#[test]
+fn no_strength_leaves_the_audio_alone() {
+    let input = noise(8000, 0.5);
+    let mut suppressor = NoiseSuppressor::new(SAMPLE_RATE, 1, 0.0);
+    let output = process(&mut suppressor, &input);
+    // half a 512 sample frame behind
+    let delay = 256;
+    assert!(output.len() > 7000);
+    for (out, expected) in output[delay..].iter().zip(&input) {
+        assert!((out - expected).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn steady_noise_is_attenuated_and_a_tone_over_it_kept() {
+    let mut suppressor = NoiseSuppressor::new(SAMPLE_RATE, 1, 1.0);
+    let background = noise(32_000, 0.05);
+    let output = process(&mut suppressor, &background);
+    // once the noise has been learnt
+    let reduction = level(&background[16_000..]) - level(&output[16_000..]);
+    assert!(reduction > 15.0, "noise only down by {reduction} dB");
+
+    let clean = tone(16_000, 0.5);
+    let noisy: Vec<f32> = clean
+        .iter()
+        .zip(noise(16_000, 0.05))
+        .map(|(a, b)| a + b)
+        .collect();
+    let output = process(&mut suppressor, &noisy);
+    let kept = level(&clean[4000..12_000]) - level(&output[4000..12_000]);
+    assert!(kept.abs() < 1.0, "tone changed by {kept} dB");
+}
+
+#[test]
+fn channels_are_kept_apart() {
+    let left = noise(8000, 0.5);
+    let interleaved: Vec<f32> = left.iter().flat_map(|f| [*f, 0.0]).collect();
+    let mut suppressor = NoiseSuppressor::new(SAMPLE_RATE, 2, 0.5);
+    let output = process(&mut suppressor, &interleaved);
+    assert_eq!(output.len() % 2, 0);
+    assert!(output.iter().skip(1).step_by(2).all(|f| f.abs() < 1e-6));
+    assert!(output.iter().step_by(2).any(|f| f.abs() > 0.01));
+}
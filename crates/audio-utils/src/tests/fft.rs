@@ -0,0 +1,195 @@
+use std::f32::consts::PI;
+
+use crate::fft::{merge_buffers, merge_latest, SpectrumAnalyser, SpectrumSettings};
+
+fn settings() -> SpectrumSettings {
+    SpectrumSettings {
+        max_frequency: 11025,
+        volume: 1.0,
+        frequency_scale_range: 50..1000,
+        smoothing_amount: 5,
+        smoothing_size: 10,
+        frequency_scale_amount: 1,
+    }
+}
+
+fn frame(frequency: f32, size: usize) -> Vec<f32> {
+    (0..size)
+        .map(|f| (2.0 * PI * frequency * f as f32 / 44_100.0).sin())
+        .collect()
+}
+
+// what `convert_buffer` gave for `chord()` and `frame(1000.0, 256)` before the analyser
+// replaced its internals
+const CHORD_SPECTRUM: [f32; 39] = [
+    0.002665045,
+    0.0024596541,
+    0.0020736377,
+    0.0017323926,
+    0.0015411978,
+    0.0012701754,
+    0.0010940265,
+    0.0010453168,
+    0.00097237085,
+    0.0010350622,
+    0.0012992604,
+    0.0017982672,
+    0.0019601996,
+    0.0028008574,
+    0.003739937,
+    0.0049616443,
+    0.0055139544,
+    0.007096709,
+    0.008588,
+    0.009887066,
+    0.010572711,
+    0.011518662,
+    0.011549125,
+    0.010607866,
+    0.010244751,
+    0.008422732,
+    0.0059418557,
+    0.0036579582,
+    0.0026530216,
+    0.0009450109,
+    0.0002567032,
+    6.754048e-5,
+    4.2282503e-5,
+    2.2356973e-5,
+    1.50518035e-5,
+    1.2883436e-5,
+    1.2593428e-5,
+    8.047424e-6,
+    6.616719e-6,
+];
+const TONE_SPECTRUM: [f32; 39] = [
+    0.0052283183,
+    0.0053274953,
+    0.005294704,
+    0.0051876972,
+    0.0051757745,
+    0.0049001854,
+    0.004515269,
+    0.004052488,
+    0.0038512563,
+    0.0033034242,
+    0.0027315225,
+    0.0021820317,
+    0.001900049,
+    0.0013789749,
+    0.00095953007,
+    0.0006371063,
+    0.00046996746,
+    0.000257016,
+    0.00013164902,
+    6.447682e-5,
+    3.6834786e-5,
+    1.6341757e-5,
+    9.833846e-6,
+    7.6910055e-6,
+    6.8775585e-6,
+    5.677705e-6,
+    4.780442e-6,
+    4.1006924e-6,
+    3.8221597e-6,
+    3.24549e-6,
+    2.8913335e-6,
+    2.5545535e-6,
+    2.4310925e-6,
+    2.058405e-6,
+    1.8416656e-6,
+    1.763686e-6,
+    1.7514175e-6,
+    1.4570998e-6,
+    1.3520482e-6,
+];
+
+fn chord() -> Vec<f32> {
+    frame(440.0, 256)
+        .iter()
+        .zip(frame(3000.0, 256))
+        .map(|(low, high)| low + 0.5 * high)
+        .collect()
+}
+
+fn assert_spectrum(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len());
+    // the quietest bins are down in rounding noise, so the margin follows the loudest one
+    let margin = 1e-4 * expected.iter().fold(0.0f32, |max, f| max.max(*f));
+    for (i, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+        assert!(
+            (actual - expected).abs() <= margin,
+            "bin {i} is {actual}, expected {expected}"
+        );
+    }
+}
+
+#[test]
+fn analyser_matches_the_original_conversion() {
+    let settings = settings();
+    let mut analyser = SpectrumAnalyser::new(256);
+    assert_spectrum(analyser.process(&chord(), &settings), &CHORD_SPECTRUM);
+    assert_spectrum(
+        analyser.process(&frame(1000.0, 256), &settings),
+        &TONE_SPECTRUM,
+    );
+    // nothing from earlier frames carries over
+    assert_spectrum(analyser.process(&chord(), &settings), &CHORD_SPECTRUM);
+}
+
+#[test]
+fn settings_can_change_between_frames() {
+    let mut analyser = SpectrumAnalyser::new(2048);
+    let frame = frame(440.0, 2048);
+    let quiet = analyser.process(&frame, &settings()).to_vec();
+    let loud = analyser
+        .process(
+            &frame,
+            &SpectrumSettings {
+                volume: 2.0,
+                ..settings()
+            },
+        )
+        .to_vec();
+    assert_eq!(quiet.len(), loud.len());
+    for (quiet, loud) in quiet.iter().zip(&loud) {
+        assert!((quiet * 2.0 - loud).abs() <= 1e-4 * loud.abs().max(1.0));
+    }
+}
+
+#[test]
+fn higher_tones_peak_further_along() {
+    let mut analyser = SpectrumAnalyser::new(4096);
+    let settings = SpectrumSettings {
+        smoothing_amount: 0,
+        ..settings()
+    };
+    let peaks: Vec<usize> = [300.0, 1500.0, 6000.0]
+        .into_iter()
+        .map(|frequency| {
+            let spectrum = analyser.process(&frame(frequency, 4096), &settings);
+            spectrum
+                .iter()
+                .enumerate()
+                .fold(
+                    (0, 0.0),
+                    |peak, (i, f)| if *f > peak.1 { (i, *f) } else { peak },
+                )
+                .0
+        })
+        .collect();
+    assert!(peaks.windows(2).all(|f| f[0] < f[1]), "peaks at {peaks:?}");
+}
+
+#[test]
+fn merging_in_place_matches_merging_buffers() {
+    let earlier = frame(300.0, 64);
+    let latest = frame(700.0, 64);
+    let mut merged = earlier.clone();
+    merge_latest(&mut merged, &latest);
+    assert_eq!(merged, merge_buffers(&[earlier, latest.clone()]));
+
+    let mut merged = vec![];
+    merge_latest(&mut merged, &latest);
+    assert_eq!(merged, latest);
+}
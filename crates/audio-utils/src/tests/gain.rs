@@ -0,0 +1,68 @@
+use std::f32::consts::PI;
+
+use crate::gain::{AgcSettings, AutomaticGainControl};
+
+const SAMPLE_RATE: f32 = 16_000.0;
+
+// a sine with the given rms level in dBFS
+fn tone(seconds: f32, level: f32) -> Vec<f32> {
+    let amplitude = 10f32.powf(level / 20.0) * 2f32.sqrt();
+    (0..(SAMPLE_RATE * seconds) as usize)
+        .map(|i| (2.0 * PI * 300.0 * i as f32 / SAMPLE_RATE).sin() * amplitude)
+        .collect()
+}
+
+fn level(samples: &[f32]) -> f32 {
+    let power = samples.iter().map(|f| f * f).sum::<f32>() / samples.len() as f32;
+    10.0 * power.log10()
+}
+
+fn process(agc: &mut AutomaticGainControl, mut samples: Vec<f32>) -> Vec<f32> {
+    for chunk in samples.chunks_mut(160) {
+        agc.process(chunk);
+    }
+    samples
+}
+
+#[test]
+fn quiet_and_loud_inputs_end_up_at_the_target() {
+    for input in [-45.0, -6.0] {
+        let mut agc = AutomaticGainControl::new(SAMPLE_RATE, 1, AgcSettings::default());
+        let output = process(&mut agc, tone(8.0, input));
+        let settled = level(&output[output.len() - 16_000..]);
+        assert!(
+            (settled + 20.0).abs() < 1.5,
+            "{input} dBFS came out at {settled}"
+        );
+    }
+}
+
+#[test]
+fn gain_is_capped() {
+    let settings = AgcSettings {
+        max_gain: 10.0,
+        ..AgcSettings::default()
+    };
+    let mut agc = AutomaticGainControl::new(SAMPLE_RATE, 1, settings);
+    let output = process(&mut agc, tone(8.0, -50.0));
+    let settled = level(&output[output.len() - 16_000..]);
+    assert!((settled + 40.0).abs() < 0.5, "came out at {settled}");
+}
+
+#[test]
+fn sudden_peaks_do_not_clip() {
+    let mut agc = AutomaticGainControl::new(SAMPLE_RATE, 1, AgcSettings::default());
+    let mut input = tone(5.0, -45.0);
+    input.extend(tone(1.0, -3.0));
+    let output = process(&mut agc, input);
+    assert!(output
+        .iter()
+        .all(|f| f.abs() <= 10f32.powf(-1.0 / 20.0) + 1e-6));
+}
+
+#[test]
+fn silence_is_not_turned_up() {
+    let mut agc = AutomaticGainControl::new(SAMPLE_RATE, 2, AgcSettings::default());
+    process(&mut agc, vec![0.0; 32_000]);
+    assert_eq!(agc.gain(), 0.0);
+}
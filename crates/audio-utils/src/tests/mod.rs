@@ -0,0 +1,5 @@
+mod denoise;
+mod fft;
+mod gain;
+mod resample;
+mod vad;
@@ -0,0 +1,72 @@
+use std::f32::consts::PI;
+
+use crate::resample::Resampler;
+
+fn sine(frequency: f32, sample_rate: f32, seconds: f32) -> Vec<f32> {
+    (0..(sample_rate * seconds) as usize)
+        .map(|f| (2.0 * PI * frequency * f as f32 / sample_rate).sin())
+        .collect()
+}
+
+// skips the start, where the filter is still filling up
+fn peak(samples: &[f32]) -> f32 {
+    samples[samples.len() / 4..]
+        .iter()
+        .fold(0.0, |peak, f| f.abs().max(peak))
+}
+
+#[test]
+fn rates_are_converted() {
+    for (from, to) in [
+        (48_000.0, 16_000.0),
+        (44_100.0, 16_000.0),
+        (8_000.0, 16_000.0),
+    ] {
+        let output = Resampler::new(from, to).process(&sine(440.0, from, 1.0));
+        // a second in, a second out, less what the filter holds back of a few milliseconds
+        let held_back = to as usize - output.len();
+        assert!(
+            held_back < to as usize / 200,
+            "{from} to {to} gave {} samples",
+            output.len()
+        );
+    }
+}
+
+#[test]
+fn chunks_do_not_change_the_output() {
+    let input = sine(1000.0, 44_100.0, 0.5);
+    let whole = Resampler::new(44_100.0, 16_000.0).process(&input);
+
+    let mut resampler = Resampler::new(44_100.0, 16_000.0);
+    let chunked: Vec<f32> = input
+        .chunks(333)
+        .flat_map(|f| resampler.process(f))
+        .collect();
+    assert_eq!(whole.len(), chunked.len());
+    assert!(whole
+        .iter()
+        .zip(&chunked)
+        .all(|(a, b)| (a - b).abs() < 1e-5));
+}
+
+#[test]
+fn speech_band_passes_and_aliases_are_removed() {
+    let mut resampler = Resampler::new(48_000.0, 16_000.0);
+    let kept = peak(&resampler.process(&sine(1000.0, 48_000.0, 1.0)));
+    assert!((kept - 1.0).abs() < 0.02, "1 kHz came out at {kept}");
+
+    // above the 8 kHz nyquist frequency of the output, it would fold back to 4 kHz
+    resampler.reset();
+    let removed = peak(&resampler.process(&sine(12_000.0, 48_000.0, 1.0)));
+    assert!(removed < 0.01, "12 kHz came out at {removed}");
+}
+
+#[test]
+fn same_rate_is_untouched() {
+    let input = [1, -2, 3, i16::MAX, i16::MIN];
+    assert_eq!(
+        Resampler::new(16_000.0, 16_000.0).process_i16(&input),
+        input
+    );
+}
@@ -0,0 +1,91 @@
+use std::f32::consts::PI;
+
+use crate::vad::{VadSettings, VoiceActivity, VoiceActivityDetector};
+
+const SAMPLE_RATE: f32 = 16_000.0;
+
+// a tenth of a second, about what a microphone hands over at a time
+const CHUNK: usize = 1600;
+
+// a 120 Hz voice with its harmonics
+fn voice(chunks: usize, amplitude: f32) -> Vec<i16> {
+    (0..chunks * CHUNK)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE;
+            let sample: f32 = (1..=10)
+                .map(|h| (2.0 * PI * 120.0 * h as f32 * t).sin() / h as f32)
+                .sum();
+            (sample * amplitude * 0.5) as i16
+        })
+        .collect()
+}
+
+fn noise(chunks: usize, amplitude: f32) -> Vec<i16> {
+    let mut state: u32 = 0x1234_5678;
+    (0..chunks * CHUNK)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            ((state >> 16) as f32 / 32768.0 - 1.0) * amplitude
+        })
+        .map(|f| f as i16)
+        .collect()
+}
+
+fn activity(detector: &mut VoiceActivityDetector, audio: &[i16]) -> Vec<VoiceActivity> {
+    audio.chunks(CHUNK).map(|f| detector.process(f)).collect()
+}
+
+#[test]
+fn voice_over_background_noise_is_speech() {
+    let mut detector = VoiceActivityDetector::new(SAMPLE_RATE, VadSettings::default());
+    assert!(activity(&mut detector, &noise(10, 200.0))
+        .iter()
+        .all(|f| *f == VoiceActivity::Silence));
+
+    let mut audio = voice(5, 8000.0);
+    for (sample, noise) in audio.iter_mut().zip(noise(5, 200.0)) {
+        *sample += noise;
+    }
+    let heard = activity(&mut detector, &audio);
+    assert!(
+        heard.iter().all(|f| *f == VoiceActivity::Speech),
+        "{heard:?}"
+    );
+}
+
+#[test]
+fn loud_hiss_is_not_speech() {
+    let mut detector = VoiceActivityDetector::new(SAMPLE_RATE, VadSettings::default());
+    activity(&mut detector, &noise(5, 100.0));
+    let heard = activity(&mut detector, &noise(5, 10_000.0));
+    assert!(
+        heard.iter().all(|f| *f == VoiceActivity::Silence),
+        "{heard:?}"
+    );
+}
+
+#[test]
+fn speech_carries_on_through_the_hangover() {
+    let settings = VadSettings::default();
+    let mut detector = VoiceActivityDetector::new(SAMPLE_RATE, settings);
+    activity(&mut detector, &vec![0; 5 * CHUNK]);
+    activity(&mut detector, &voice(5, 8000.0));
+    assert_eq!(detector.activity(), VoiceActivity::Speech);
+
+    // a pause shorter than the hangover
+    let heard = activity(&mut detector, &vec![0; 3 * CHUNK]);
+    assert_eq!(heard, [VoiceActivity::Speech; 3]);
+    let heard = activity(&mut detector, &vec![0; 5 * CHUNK]);
+    assert_eq!(heard.last(), Some(&VoiceActivity::Silence));
+}
+
+#[test]
+fn short_clicks_are_ignored() {
+    let mut detector = VoiceActivityDetector::new(SAMPLE_RATE, VadSettings::default());
+    let mut audio = vec![0; 5 * CHUNK];
+    // 20 ms, shorter than the onset
+    audio[2 * CHUNK..2 * CHUNK + 320].copy_from_slice(&voice(1, 8000.0)[..320]);
+    assert!(activity(&mut detector, &audio)
+        .iter()
+        .all(|f| *f == VoiceActivity::Silence));
+}
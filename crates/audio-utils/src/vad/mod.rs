@@ -0,0 +1,187 @@
+use std::{sync::Arc, time::Duration};
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::window::hann_window;
+
+// length of the frames features are taken over
+const FRAME: Duration = Duration::from_millis(20);
+
+// frames quieter than this, in dBFS, are silence however quiet the room is
+const MIN_LEVEL: f32 = -60.0;
+
+// white noise sits around 0.56, voiced speech well below that
+const MAX_FLATNESS: f32 = 0.3;
+
+// fraction of samples changing sign, hiss crosses zero about every other sample
+const MAX_ZERO_CROSSINGS: f32 = 0.25;
+
+// how quickly the noise floor follows the level of frames that are not speech
+const NOISE_ADAPTATION: f32 = 0.05;
+
+// and of frames that are, slow enough to take a steady hum half a minute to stop counting
+const SPEECH_ADAPTATION: f32 = 0.001;
+
+/// What a stretch of audio was judged to be
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VoiceActivity {
+    #[default]
+    Silence,
+    Speech,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VadSettings {
+    /// How far above the background noise, in decibels, a frame has to be to count as speech
+    pub threshold: f32,
+    /// How long speech has to go on for before it is reported, so clicks and knocks are not
+    pub onset: Duration,
+    /// How long speech is still reported after it stops, bridging pauses between words
+    pub hangover: Duration,
+}
+
+impl Default for VadSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 9.0,
+            onset: Duration::from_millis(60),
+            hangover: Duration::from_millis(400),
+        }
+    }
+}
+
+/// Streaming voice activity detection over 16 bit mono audio. A frame is speech when it is loud
+/// enough over the noise floor and either tonal or low in zero crossings, as hiss and fan noise
+/// are neither
+pub struct VoiceActivityDetector {
+    settings: VadSettings,
+    frame: usize,
+    fft: Arc<dyn Fft<f32>>,
+    // samples left over from the last chunk, short of a whole frame
+    pending: Vec<f32>,
+    // running estimate of the background level in dBFS, set by the first frame
+    noise_floor: Option<f32>,
+    onset_frames: usize,
+    hangover_frames: usize,
+    // consecutive frames that sounded like speech
+    run: usize,
+    // frames of hangover left
+    hangover: usize,
+    activity: VoiceActivity,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(sample_rate: f32, settings: VadSettings) -> Self {
+        let frame = (sample_rate * FRAME.as_secs_f32()).max(1.0) as usize;
+        let frames = |f: Duration| (f.as_secs_f32() / FRAME.as_secs_f32()).ceil() as usize;
+        Self {
+            settings,
+            frame,
+            fft: FftPlanner::new().plan_fft_forward(frame),
+            pending: Vec::with_capacity(frame),
+            noise_floor: None,
+            onset_frames: frames(settings.onset).max(1),
+            hangover_frames: frames(settings.hangover),
+            run: 0,
+            hangover: 0,
+            activity: VoiceActivity::Silence,
+        }
+    }
+
+    /// Speech if any part of the chunk was, so the chunk that speech starts or ends in is kept
+    pub fn process(&mut self, chunk: &[i16]) -> VoiceActivity {
+        let mut activity = self.activity;
+        self.pending
+            .extend(chunk.iter().map(|f| f32::from(*f) / 32768.0));
+        let mut start = 0;
+        while start + self.frame <= self.pending.len() {
+            let frame = self.pending[start..start + self.frame].to_vec();
+            self.update(&frame);
+            if self.activity == VoiceActivity::Speech {
+                activity = VoiceActivity::Speech;
+            }
+            start += self.frame;
+        }
+        self.pending.drain(..start);
+        activity
+    }
+
+    /// Where the detector is now, after the last frame it saw
+    pub fn activity(&self) -> VoiceActivity {
+        self.activity
+    }
+
+    /// Starts over, the noise floor is learnt again
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.noise_floor = None;
+        self.run = 0;
+        self.hangover = 0;
+        self.activity = VoiceActivity::Silence;
+    }
+
+    fn update(&mut self, frame: &[f32]) {
+        let level = level(frame);
+        let noise_floor = *self.noise_floor.get_or_insert(level);
+        let loud = level > MIN_LEVEL && level - noise_floor > self.settings.threshold;
+        let speech = loud
+            && (self.flatness(frame) < MAX_FLATNESS || zero_crossings(frame) < MAX_ZERO_CROSSINGS);
+
+        if speech {
+            self.noise_floor = Some(noise_floor + (level - noise_floor) * SPEECH_ADAPTATION);
+            self.run += 1;
+            if self.run >= self.onset_frames {
+                self.activity = VoiceActivity::Speech;
+                self.hangover = self.hangover_frames;
+            }
+        } else {
+            self.run = 0;
+            // drops straight to quieter levels, rises slowly so speech does not drag it up
+            self.noise_floor = Some(if level < noise_floor {
+                level
+            } else {
+                noise_floor + (level - noise_floor) * NOISE_ADAPTATION
+            });
+            if self.hangover > 0 {
+                self.hangover -= 1;
+            } else {
+                self.activity = VoiceActivity::Silence;
+            }
+        }
+    }
+
+    // geometric over arithmetic mean of the power spectrum, 1 for noise and near 0 for tones
+    fn flatness(&self, frame: &[f32]) -> f32 {
+        let mut buffer: Vec<Complex<f32>> = hann_window(frame)
+            .into_iter()
+            .map(|f| Complex { re: f, im: 0.0 })
+            .collect();
+        self.fft.process(&mut buffer);
+        // dc says nothing about the shape of the spectrum
+        let power: Vec<f32> = buffer[1..=buffer.len() / 2]
+            .iter()
+            .map(|f| f.norm_sqr() + 1e-12)
+            .collect();
+        if power.is_empty() {
+            return 1.0;
+        }
+        let count = power.len() as f32;
+        let geometric = (power.iter().map(|f| f.ln()).sum::<f32>() / count).exp();
+        let arithmetic = power.iter().sum::<f32>() / count;
+        geometric / arithmetic
+    }
+}
+
+// root mean square in dBFS
+fn level(frame: &[f32]) -> f32 {
+    let power = frame.iter().map(|f| f * f).sum::<f32>() / frame.len().max(1) as f32;
+    10.0 * (power + 1e-10).log10()
+}
+
+fn zero_crossings(frame: &[f32]) -> f32 {
+    let crossings = frame
+        .windows(2)
+        .filter(|f| (f[0] >= 0.0) != (f[1] >= 0.0))
+        .count();
+    crossings as f32 / frame.len().max(1) as f32
+}
@@ -1,14 +1,20 @@
 use std::f32::consts::PI;
 
 pub fn hann_window(samples: &[f32]) -> Vec<f32> {
-    let len = samples.len() as f32;
     samples
         .iter()
-        .enumerate()
-        .map(|(n, sample)| {
+        .zip(hann_coefficients(samples.len()))
+        .map(|(sample, multiplier)| multiplier * sample)
+        .collect()
+}
+
+/// The multipliers `hann_window` applies to a buffer of `len` samples
+pub fn hann_coefficients(len: usize) -> Vec<f32> {
+    let len_f = len as f32;
+    (0..len)
+        .map(|n| {
             let two_pi_i = 2.0 * PI * n as f32;
-            let multiplier = 0.5 * (1.0 - f32::cos(two_pi_i / len));
-            multiplier * sample
+            0.5 * (1.0 - f32::cos(two_pi_i / len_f))
         })
         .collect()
 }
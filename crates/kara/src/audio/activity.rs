@@ -0,0 +1,120 @@
+use std::{collections::VecDeque, time::Duration};
+
+use asr::{sources::SpeechRecognisers, TranscriptionError, TranscriptionResult};
+use audio_utils::vad::{VoiceActivity, VoiceActivityDetector};
+use crossbeam_channel::Sender;
+use tracing::{debug, error, info, trace};
+
+use crate::config::{Configuration, VoiceActivityDetection};
+
+// audio from before speech was detected that is still handed over, so first words are not clipped
+const PRE_ROLL: Duration = Duration::from_millis(300);
+
+/// Only lets speech through to the recognisers. Each stretch of it is its own utterance, which
+/// is finalised as soon as the speaker stops
+pub struct SpeechGate {
+    sample_rate: f32,
+    settings: Option<VoiceActivityDetection>,
+    detector: Option<VoiceActivityDetector>,
+    activity: VoiceActivity,
+    pre_roll: VecDeque<Vec<i16>>,
+    // no backend is taking the audio, reported when it starts and stops rather than every chunk
+    unheard: bool,
+}
+
+impl SpeechGate {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            settings: None,
+            detector: None,
+            activity: VoiceActivity::Silence,
+            pre_roll: VecDeque::new(),
+            unheard: false,
+        }
+    }
+
+    /// Starts over with new settings when they change in the configuration
+    pub fn follow_config(&mut self, config: &Configuration) {
+        let settings = config
+            .audio
+            .as_ref()
+            .map(|f| f.voice_activity)
+            .unwrap_or_default();
+        if self.settings == Some(settings) {
+            return;
+        }
+        debug!(settings = ?settings, "voice activity detection changed");
+        self.settings = Some(settings);
+        self.detector = settings
+            .enabled
+            .then(|| VoiceActivityDetector::new(self.sample_rate, settings.settings()));
+        self.pre_roll.clear();
+    }
+
+    /// Feeds the recognisers if the chunk has speech in it and returns the new activity when it
+    /// changed
+    pub fn feed(
+        &mut self,
+        samples: Vec<i16>,
+        recognisers: &mut SpeechRecognisers,
+        result_sender: &Sender<TranscriptionResult>,
+    ) -> Option<VoiceActivity> {
+        let Some(detector) = &mut self.detector else {
+            // everything is heard as one long utterance
+            let changed = self.set_activity(VoiceActivity::Speech);
+            let result = recognisers.speech_to_text(&samples, result_sender);
+            self.report(result);
+            return changed;
+        };
+
+        let activity = detector.process(&samples);
+        let changed = self.set_activity(activity);
+        match activity {
+            VoiceActivity::Speech => {
+                if changed.is_some() {
+                    trace!("speech started");
+                    recognisers.begin_utterance();
+                }
+                let pre_roll = std::mem::take(&mut self.pre_roll);
+                for chunk in pre_roll.into_iter().chain(std::iter::once(samples)) {
+                    let result = recognisers.speech_to_text(&chunk, result_sender);
+                    self.report(result);
+                }
+            }
+            VoiceActivity::Silence => {
+                if changed.is_some() {
+                    trace!("speech stopped");
+                    recognisers.end_utterance(result_sender);
+                }
+                self.pre_roll.push_back(samples);
+                let limit = (self.sample_rate * PRE_ROLL.as_secs_f32()) as usize;
+                while self.pre_roll.iter().map(Vec::len).sum::<usize>() > limit {
+                    self.pre_roll.pop_front();
+                }
+            }
+        }
+        changed
+    }
+
+    fn report(&mut self, result: Result<(), TranscriptionError>) {
+        match result {
+            Ok(()) if self.unheard => {
+                self.unheard = false;
+                info!("speech recognition is taking audio again");
+            }
+            Err(e) if !self.unheard => {
+                self.unheard = true;
+                error!("{e}");
+            }
+            _ => {}
+        }
+    }
+
+    fn set_activity(&mut self, activity: VoiceActivity) -> Option<VoiceActivity> {
+        (self.activity != activity).then(|| {
+            self.activity = activity;
+            activity
+        })
+    }
+}
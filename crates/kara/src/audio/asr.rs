@@ -1,33 +1,123 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::{
-    path::Path,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
-use asr::sources::kara::LocalRecogniser;
+use asr::{
+    sources::{
+        kara::{LocalOptions, LocalRecogniser},
+        SpeechRecognisers,
+    },
+    Transcibe,
+};
 use crossbeam_channel::Sender;
 use iced_winit::winit::event_loop::EventLoopProxy;
+use res_def::models::{find_model, ModelEntry};
 use res_get::ResGet;
 use tracing::{debug, error};
 
 use crate::events::KaraEvent;
 
+/// Where a local model is installed, along with its catalogue entry when it was referenced by id
+pub fn local_model_location(
+    model: Option<&str>,
+    model_path: &Path,
+) -> Result<(PathBuf, Option<&'static ModelEntry>)> {
+    match model {
+        Some(id) => {
+            let entry = find_model(id).ok_or_else(|| anyhow!("{id} is not a known model"))?;
+            Ok((entry.install_path(), Some(entry)))
+        }
+        None if model_path.as_os_str().is_empty() => Ok((res_def::model_path(), None)),
+        None => Ok((model_path.to_owned(), None)),
+    }
+}
+
+/// Backends in the order they are configured. A local source on a model an earlier one already
+/// uses, with the same options, becomes another session of it and shares its recognisers and
+/// voice profiles
+#[derive(Default)]
+pub struct ConfiguredBackends {
+    backends: Vec<(bool, Configured)>,
+}
+
+enum Configured {
+    Local {
+        model_path: PathBuf,
+        options: LocalOptions,
+        recogniser: LocalRecogniser,
+    },
+    Other(Box<dyn Transcibe>),
+}
+
+impl ConfiguredBackends {
+    /// Another session of a local recogniser already created for the model
+    pub fn session(&self, model_path: &Path, options: &LocalOptions) -> Option<LocalRecogniser> {
+        self.backends.iter().find_map(|(_, f)| match f {
+            Configured::Local {
+                model_path: path,
+                options: existing,
+                recogniser,
+            } if path == model_path && existing == options => {
+                debug!(path = ?model_path, "sharing local model with another source");
+                recogniser.session().map_err(|e| error!("{e}")).ok()
+            }
+            _ => None,
+        })
+    }
+
+    pub fn add_local(
+        &mut self,
+        primary: bool,
+        model_path: PathBuf,
+        options: LocalOptions,
+        recogniser: LocalRecogniser,
+    ) {
+        let local = Configured::Local {
+            model_path,
+            options,
+            recogniser,
+        };
+        self.backends.push((primary, local));
+    }
+
+    pub fn add(&mut self, primary: bool, backend: Box<dyn Transcibe>) {
+        self.backends.push((primary, Configured::Other(backend)));
+    }
+
+    pub fn into_recognisers(self) -> SpeechRecognisers {
+        let mut speech_recognisers = SpeechRecognisers::new();
+        for (primary, backend) in self.backends {
+            let backend = match backend {
+                Configured::Local { recogniser, .. } => Box::new(recogniser),
+                Configured::Other(backend) => backend,
+            };
+            if primary {
+                speech_recognisers.add_primary(backend);
+            } else {
+                speech_recognisers.add(backend);
+            }
+        }
+        speech_recognisers
+    }
+}
+
 pub fn try_default_location(
     model_path: impl AsRef<Path> + std::marker::Send,
-    sample_rate: f32,
+    options: &LocalOptions,
 ) -> Result<LocalRecogniser> {
-    Ok(LocalRecogniser::new(model_path, sample_rate)?)
+    Ok(LocalRecogniser::new(model_path, options)?)
 }
 
 pub async fn get_remote_model(
     event_loop: Arc<Mutex<EventLoopProxy<KaraEvent>>>,
     sender: Sender<LocalRecogniser>,
-    fallback_url: impl AsRef<str>,
+    res_get: ResGet,
     model_path: impl AsRef<Path>,
-    sample_rate: f32,
+    options: LocalOptions,
 ) -> Result<()> {
     let model_path = model_path.as_ref().to_owned();
-    let res_get = ResGet::new(fallback_url.as_ref(), &model_path);
     let progress = res_get.get_progress().clone();
     tokio::spawn(async move {
         // send with sender
@@ -36,12 +126,12 @@ pub async fn get_remote_model(
             "trying default sender"
         );
 
-        if let Err(e) = try_default_location(&model_path, sample_rate).map(|model| {
+        if let Err(e) = try_default_location(&model_path, &options).map(|model| {
             let _ = sender.send(model);
         }) {
             error!("{e}");
             if let Err(e) = res_get.get_asr_model().await.and_then(|()| {
-                if let Err(e) = try_default_location(model_path, sample_rate).map(|model| {
+                if let Err(e) = try_default_location(model_path, &options).map(|model| {
                     let _ = sender.send(model);
                 }) {
                     error!("read model error 1: {e}");
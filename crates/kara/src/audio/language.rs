@@ -0,0 +1,213 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use asr::{
+    sources::{
+        kara::{LocalOptions, LocalRecogniser},
+        language::LanguageIdentifier,
+        Source, SpeechRecognisers,
+    },
+    Transcibe, TranscriptionResult,
+};
+use crossbeam_channel::{Receiver, Sender};
+use res_def::models::{installed_model_for, installed_models, language_tag, ModelEntry};
+use tracing::{debug, error, info, warn};
+
+use crate::config::Configuration;
+
+/// Loads the model for another language away from the audio thread and swaps it in once it is
+/// ready, so listening carries on while it loads
+pub struct LanguageSwitcher {
+    sample_rate: f32,
+    // last language the configuration asked for
+    configured: Option<String>,
+    // model id, or `AUTOMATIC`, of the latest request. Asking for it again does nothing
+    requested: Option<String>,
+    // counts requests, a load finishing after a newer request was made is dropped
+    generation: Arc<AtomicU64>,
+    loaded_sender: Sender<Loaded>,
+    loaded: Receiver<Loaded>,
+}
+
+// the request a load was for and the model, if it could be loaded
+type Loaded = (u64, Option<Box<dyn Transcibe>>);
+
+/// Asks for the language to be identified from the installed models on every utterance
+pub const AUTOMATIC: &str = "auto";
+
+impl LanguageSwitcher {
+    pub fn new(sample_rate: f32) -> Self {
+        let (loaded_sender, loaded) = crossbeam_channel::unbounded();
+        Self {
+            sample_rate,
+            configured: None,
+            requested: None,
+            generation: Arc::default(),
+            loaded_sender,
+            loaded,
+        }
+    }
+
+    /// Switches when `language` changes in the configuration
+    pub fn follow_config(&mut self, config: &Configuration) {
+        let language = &config.speech_recognition.language;
+        if *language == self.configured {
+            return;
+        }
+        self.configured = language.clone();
+        if let Some(language) = language {
+            self.request(language, local_options(config));
+        }
+    }
+
+    /// Starts loading an installed model for the language, `en` picks any English model. A
+    /// request for the model already loading or in use is ignored, any other one supersedes it
+    pub fn request(&mut self, language: &str, options: LocalOptions) {
+        let model = match language {
+            AUTOMATIC => None,
+            language => match installed_model_for(language) {
+                Some(model) => Some(model),
+                None => {
+                    warn!(language = language, "no model installed for this language");
+                    return;
+                }
+            },
+        };
+        let key = model.map_or(AUTOMATIC, |f| f.id);
+        if self.requested.as_deref() == Some(key) {
+            debug!(language = language, "language already requested");
+            return;
+        }
+        self.requested = Some(key.to_owned());
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let current = Arc::clone(&self.generation);
+        let sender = self.loaded_sender.clone();
+        match model {
+            Some(model) => {
+                info!(language = language, model = model.id, "switching language");
+                std::thread::spawn(move || {
+                    let recogniser = match LocalRecogniser::new(model.install_path(), &options) {
+                        Ok(recogniser) => Some(Box::new(recogniser) as Box<dyn Transcibe>),
+                        Err(e) => {
+                            error!(model = model.id, "{e}");
+                            None
+                        }
+                    };
+                    let _ = sender.send((generation, recogniser));
+                });
+            }
+            None => {
+                let sample_rate = self.sample_rate;
+                let models = automatic_models();
+                std::thread::spawn(move || {
+                    let superseded = || current.load(Ordering::SeqCst) != generation;
+                    let identifier = load_identifier(models, options, sample_rate, superseded);
+                    let _ = sender.send((generation, identifier));
+                });
+            }
+        }
+    }
+
+    /// Puts the model of the latest request in place of the local backend once it has loaded
+    pub fn swap(
+        &mut self,
+        recognisers: &mut SpeechRecognisers,
+        result_sender: &Sender<TranscriptionResult>,
+    ) {
+        for (generation, recogniser) in self.loaded.try_iter() {
+            if generation != self.generation.load(Ordering::SeqCst) {
+                debug!("dropping a language model that was superseded while loading");
+                continue;
+            }
+            match recogniser {
+                Some(recogniser) => recognisers.replace(recogniser, result_sender),
+                // it can be asked for again
+                None => self.requested = None,
+            }
+        }
+    }
+}
+
+// one installed model per language, they compete for every utterance
+fn automatic_models() -> Vec<&'static ModelEntry> {
+    let mut models: Vec<&ModelEntry> = Vec::new();
+    for model in installed_models() {
+        let primary = |tag: &str| tag.split('-').next().unwrap_or(tag).to_lowercase();
+        if !models
+            .iter()
+            .any(|f| primary(f.language) == primary(model.language))
+        {
+            models.push(model);
+        }
+    }
+    if models.len() < 2 {
+        warn!("identifying languages needs models for at least two languages installed");
+    }
+    info!(
+        languages = ?models.iter().map(|f| f.language).collect::<Vec<_>>(),
+        "identifying language automatically"
+    );
+    models
+}
+
+// stops between models once a newer request makes the identifier unwanted
+fn load_identifier(
+    models: Vec<&'static ModelEntry>,
+    options: LocalOptions,
+    sample_rate: f32,
+    superseded: impl Fn() -> bool,
+) -> Option<Box<dyn Transcibe>> {
+    let mut candidates: Vec<(String, Box<dyn Transcibe>)> = Vec::new();
+    for model in models {
+        if superseded() {
+            debug!("language identification was superseded while loading");
+            return None;
+        }
+        match LocalRecogniser::new(model.install_path(), &options) {
+            Ok(recogniser) => candidates.push((model.language.to_string(), Box::new(recogniser))),
+            Err(e) => error!(model = model.id, "{e}"),
+        }
+    }
+    match LanguageIdentifier::new(candidates, sample_rate) {
+        Ok(identifier) => Some(Box::new(identifier)),
+        Err(e) => {
+            error!("{e}");
+            None
+        }
+    }
+}
+
+/// The language asked for by "switch to german" or "switch language to french", "automatic"
+/// identifies it on every utterance
+pub fn spoken_language(text: &str) -> Option<&'static str> {
+    let text = text.trim().to_lowercase();
+    let language = text
+        .strip_prefix("switch language to ")
+        .or_else(|| text.strip_prefix("switch to "))?;
+    match language.trim() {
+        "automatic" | "auto" => Some(AUTOMATIC),
+        language => language_tag(language),
+    }
+}
+
+// a new local model keeps the settings of the configured one
+pub fn local_options(config: &Configuration) -> LocalOptions {
+    config
+        .speech_recognition
+        .sources
+        .iter()
+        .find_map(|f| match f {
+            Source::Kara {
+                max_alternatives,
+                speaker_model_path,
+                ..
+            } => Some(LocalOptions {
+                max_alternatives: *max_alternatives,
+                speaker_model: speaker_model_path.clone(),
+            }),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
@@ -1,20 +1,40 @@
+pub mod activity;
 pub mod asr;
+pub mod language;
 use crate::{
-    audio::asr::{get_remote_model, try_default_location},
-    config::Configuration,
+    audio::{
+        activity::SpeechGate,
+        asr::{get_remote_model, local_model_location, try_default_location, ConfiguredBackends},
+        language::{local_options, spoken_language, LanguageSwitcher},
+    },
+    config::{self, Configuration, GainControl},
     events::KaraEvent,
     graphics::AudioEvent,
 };
 use ::asr::{
-    sources::{kara::LocalRecogniser, Source, SpeechRecognisers},
-    Transcibe,
+    history::History,
+    sources::{
+        kara::{LocalOptions, LocalRecogniser},
+        vosk_server::VoskServerRecogniser,
+        watson::WatsonRecogniser,
+        BackendState, Source, SpeechRecognisers,
+    },
+    text::{Redactor, TextProcessing},
+    RecognitionMode, Transcibe, TranscriptionResult,
 };
+use audio_utils::{denoise::NoiseSuppressor, gain::AutomaticGainControl};
 use crossbeam_channel::{Receiver, Sender};
 use iced_winit::winit::event_loop::EventLoopProxy;
 use mic_rec::StreamOpts;
-use std::sync::{Arc, Mutex};
+use res_get::ResGet;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 use tracing::{debug, error, span, trace, warn, Level};
 
+/// Remote backends are asked for audio at `sample_rate`, local ones at the rate of their model.
+/// The stream is resampled for each of them as it is fed
 pub fn create_asr_sources(
     config: Arc<Mutex<Configuration>>,
     sample_rate: f32,
@@ -31,82 +51,213 @@ pub fn create_asr_sources(
             "creating recognisers"
         );
 
-        let mut speech_recognisers = SpeechRecognisers::new();
+        let mut backends = ConfiguredBackends::default();
 
         for i in &config_file.speech_recognition.sources {
             debug!(current_source = i.to_string());
-            let backend: Option<Box<dyn Transcibe>> = match &i {
+            let primary = i.to_string() == config_file.speech_recognition.default_source;
+            match &i {
                 Source::Kara {
+                    model,
                     model_path,
                     fallback_url,
+                    max_alternatives,
+                    speaker_model_path,
                 } => {
                     let span = span!(Level::TRACE, "kara");
                     let _enter = span.enter();
                     trace!("configuring local recogniser");
-                    match LocalRecogniser::new(model_path, sample_rate) {
-                        Ok(model) => Some(Box::new(model)),
+                    let options = LocalOptions {
+                        max_alternatives: *max_alternatives,
+                        speaker_model: speaker_model_path.clone(),
+                    };
+                    let (model_path, entry) =
+                        match local_model_location(model.as_deref(), model_path) {
+                            Ok(location) => location,
+                            Err(e) => {
+                                error!("{e}");
+                                continue;
+                            }
+                        };
+                    if let Some(session) = backends.session(&model_path, &options) {
+                        backends.add_local(primary, model_path, options, session);
+                        continue;
+                    }
+                    match LocalRecogniser::new(&model_path, &options) {
+                        Ok(model) => backends.add_local(primary, model_path, options, model),
                         Err(e) => {
                             error!(path = model_path.display().to_string(), "{e}");
-                            if i.to_string() == config_file.speech_recognition.default_source {
-                                let model_path = if model_path.as_os_str().is_empty() {
-                                    res_def::model_path()
-                                } else {
-                                    model_path.to_owned()
-                                };
-                                match try_default_location(&model_path, sample_rate) {
+                            if primary {
+                                match try_default_location(&model_path, &options) {
                                     Ok(model) => {
                                         let _ = tx_local_model.send(model);
                                     }
                                     Err(e) => {
                                         error!("{e}");
+                                        let res_get = match entry {
+                                            Some(entry) => ResGet::for_model(entry),
+                                            None => ResGet::new(fallback_url, &model_path),
+                                        };
                                         tokio::spawn(get_remote_model(
                                             Arc::clone(&event_loop),
                                             tx_local_model.clone(),
-                                            fallback_url.clone(),
+                                            res_get,
                                             model_path.clone(),
-                                            sample_rate,
+                                            options.clone(),
                                         ));
                                     }
                                 }
                             }
-                            None
                         }
                     }
                 }
-                Source::IBMWatson {
-                    api_key,
-                    service_url,
-                } => {
-                    let span = span!(Level::TRACE, "ibm_watson");
-                    let _enter = span.enter();
-                    trace!("configuring ibm watson");
-                    if api_key.is_empty() && service_url.is_empty() {
-                        warn!(source = "IBM Watson", "missing [api_key] or [service_url]");
-                        None
-                    } else {
-                        todo!("create watson instance");
+                _ => {
+                    if let Some(backend) = create_remote_backend(i, sample_rate) {
+                        backends.add(primary, backend);
                     }
                 }
-            };
-
-            if let Some(backend) = backend {
-                if i.to_string() == config_file.speech_recognition.default_source {
-                    speech_recognisers.add_primary(backend);
-                } else {
-                    speech_recognisers.add(backend);
-                }
             }
         }
+        let speech_recognisers = backends.into_recognisers();
         let _ = tx.send(speech_recognisers);
     });
     (rx, rx_local_model)
 }
 
+// every backend except the local one, which may need its model downloaded first
+fn create_remote_backend(source: &Source, sample_rate: f32) -> Option<Box<dyn Transcibe>> {
+    match source {
+        Source::Kara { .. } => None,
+        Source::IBMWatson {
+            api_key,
+            service_url,
+        } => {
+            let span = span!(Level::TRACE, "ibm_watson");
+            let _enter = span.enter();
+            trace!("configuring ibm watson");
+            if api_key.is_empty() || service_url.is_empty() {
+                warn!(source = "IBM Watson", "missing [api_key] or [service_url]");
+                None
+            } else {
+                match WatsonRecogniser::new(api_key, service_url, sample_rate) {
+                    Ok(recogniser) => Some(Box::new(recogniser)),
+                    Err(e) => {
+                        error!(url = service_url, "{e}");
+                        None
+                    }
+                }
+            }
+        }
+        Source::VoskServer {
+            url,
+            max_alternatives,
+        } => {
+            let span = span!(Level::TRACE, "vosk_server");
+            let _enter = span.enter();
+            trace!("configuring vosk-server");
+            if url.is_empty() {
+                warn!(source = "vosk-server", "missing [url]");
+                None
+            } else {
+                match VoskServerRecogniser::new(url, sample_rate, *max_alternatives) {
+                    Ok(recogniser) => Some(Box::new(recogniser)),
+                    Err(e) => {
+                        error!(url = url, "{e}");
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the configured backends for transcribing a recording. Nothing is downloaded, a
+/// missing local model is skipped
+pub fn create_file_recognisers(config: &Configuration, sample_rate: f32) -> SpeechRecognisers {
+    let mut backends = ConfiguredBackends::default();
+    for i in &config.speech_recognition.sources {
+        debug!(current_source = i.to_string());
+        let primary = i.to_string() == config.speech_recognition.default_source;
+        match i {
+            Source::Kara { .. } => {
+                let Some((model_path, options)) = local_source(i) else {
+                    continue;
+                };
+                let recogniser = match backends.session(&model_path, &options) {
+                    Some(session) => session,
+                    None => match LocalRecogniser::new(&model_path, &options) {
+                        Ok(recogniser) => recogniser,
+                        Err(e) => {
+                            error!("{e}");
+                            continue;
+                        }
+                    },
+                };
+                backends.add_local(primary, model_path, options, recogniser);
+            }
+            _ => {
+                if let Some(backend) = create_remote_backend(i, sample_rate) {
+                    backends.add(primary, backend);
+                }
+            }
+        }
+    }
+    let mut speech_recognisers = backends.into_recognisers();
+    // nothing has been heard yet so there is nothing to flush
+    let (tx, _) = crossbeam_channel::unbounded();
+    speech_recognisers.set_ensemble(config.speech_recognition.ensemble, &tx);
+    speech_recognisers.set_vocabulary(&config.speech_recognition.vocabulary, &tx);
+    speech_recognisers
+}
+
+/// Creates one configured backend without downloading anything, local models have to be installed
+pub fn create_file_backend(source: &Source, sample_rate: f32) -> Option<Box<dyn Transcibe>> {
+    match source {
+        Source::Kara { .. } => {
+            let (model_path, options) = local_source(source)?;
+            match LocalRecogniser::new(model_path, &options) {
+                Ok(model) => Some(Box::new(model)),
+                Err(e) => {
+                    error!("{e}");
+                    None
+                }
+            }
+        }
+        _ => create_remote_backend(source, sample_rate),
+    }
+}
+
+// where a local source's model is installed and what its recognisers are created with
+fn local_source(source: &Source) -> Option<(PathBuf, LocalOptions)> {
+    let Source::Kara {
+        model,
+        model_path,
+        max_alternatives,
+        speaker_model_path,
+        ..
+    } = source
+    else {
+        return None;
+    };
+    let options = LocalOptions {
+        max_alternatives: *max_alternatives,
+        speaker_model: speaker_model_path.clone(),
+    };
+    match local_model_location(model.as_deref(), model_path) {
+        Ok((model_path, _)) => Some((model_path, options)),
+        Err(e) => {
+            error!("{e}");
+            None
+        }
+    }
+}
+
 #[cfg(feature = "graphical")]
 pub fn start_listening(
     stream_opts: StreamOpts,
     config: Arc<Mutex<Configuration>>,
     event_loop: Arc<Mutex<EventLoopProxy<KaraEvent>>>,
+    config_updates: Receiver<Configuration>,
     speech_recognisers: (Receiver<SpeechRecognisers>, Receiver<LocalRecogniser>),
 ) -> Sender<AudioEvent> {
     let (visualiser_sender, event_receiver) = crossbeam_channel::unbounded();
@@ -114,7 +265,7 @@ pub fn start_listening(
 
     // blocking task that handles visualising
     use crate::graphics::visualise;
-    visualise(config, event_receiver);
+    visualise(Arc::clone(&config), event_receiver);
 
     // blocking task that listens for audio
     tokio::task::spawn_blocking(move || {
@@ -122,21 +273,66 @@ pub fn start_listening(
 
         let (speech_recognisers, local_recogniser) = speech_recognisers;
         if let Ok(mut recognisers) = speech_recognisers.recv() {
+            let mut command_grammar = None;
+            let mut backend_states = Vec::new();
+            // what the device delivers, whatever the configuration asked for
+            recognisers.set_input_rate(stream_opts.sample_rate());
+            let mut languages = LanguageSwitcher::new(stream_opts.sample_rate());
+            let mut gate = SpeechGate::new(stream_opts.sample_rate());
+            let mut text_processing = TextProcessing::default();
+            let mut redaction = Default::default();
+            let mut redactor = Redactor::default();
+            let mut history_config = None;
+            let mut history = None;
+            let mut suppressor: Option<NoiseSuppressor> = None;
+            let mut gain_control: Option<(GainControl, AutomaticGainControl)> = None;
+            // read once here, then again only when the configuration is reloaded
+            let mut settings = config
+                .lock()
+                .expect("could not acquire config lock")
+                .clone();
+            let mut reloaded = true;
+            recognisers.begin_utterance();
             while let Ok(audio_buf) = stream_opts.audio_feed().recv() {
+                if let Some(latest) = config_updates.try_iter().last() {
+                    settings = latest;
+                    reloaded = true;
+                }
+                if reloaded {
+                    reloaded = false;
+                    update_recognition_mode(&settings, &mut command_grammar, &mut recognisers, &tx);
+                    recognisers.set_ensemble(settings.speech_recognition.ensemble, &tx);
+                    recognisers.set_vocabulary(&settings.speech_recognition.vocabulary, &tx);
+                    languages.follow_config(&settings);
+                    gate.follow_config(&settings);
+                    update_noise_suppression(&settings, &stream_opts, &mut suppressor);
+                    update_gain_control(&settings, &stream_opts, &mut gain_control);
+                    text_processing = settings.speech_recognition.text_processing;
+                    if settings.speech_recognition.redaction != redaction {
+                        redaction = settings.speech_recognition.redaction.clone();
+                        redactor = Redactor::new(&redaction);
+                    }
+                    if history_config.as_ref() != Some(&settings.history) {
+                        history_config = Some(settings.history.clone());
+                        history = open_history(&settings.history);
+                    }
+                }
+                languages.swap(&mut recognisers, &tx);
+                // the visualiser is sent the audio as it was captured
+                let mut audio = match &mut suppressor {
+                    Some(suppressor) => suppressor.process(&audio_buf),
+                    None => audio_buf.clone(),
+                };
+                if let Some((_, gain_control)) = &mut gain_control {
+                    gain_control.process(&mut audio);
+                }
                 let transciption_data =
-                    audio_utils::resample_i16_mono(&audio_buf, stream_opts.channel_count());
+                    audio_utils::resample_i16_mono(&audio, stream_opts.channel_count());
                 if recognisers.valid() {
                     trace!("valid");
-                    if let Err(e) = recognisers.speech_to_text(&transciption_data, &tx) {
-                        error!("{e}");
-                    }
-                    if let Ok(ev) = rx.recv() {
+                    if let Some(activity) = gate.feed(transciption_data, &mut recognisers, &tx) {
                         let proxy = event_loop.lock().unwrap();
-                        let _ = proxy.send_event(if ev.finalised() {
-                            KaraEvent::FinalisedSpeech(ev.transcription().to_string())
-                        } else {
-                            KaraEvent::ReadingSpeech(ev.transcription().to_string())
-                        });
+                        let _ = proxy.send_event(KaraEvent::VoiceActivity(activity));
                     }
                 } else {
                     trace!("not valid");
@@ -144,14 +340,180 @@ pub fn start_listening(
                         recognisers.add_primary(Box::new(rec));
                     }
                 }
+                send_transcriptions(
+                    &rx,
+                    &event_loop,
+                    &text_processing,
+                    &redactor,
+                    &mut history,
+                    |text| {
+                        if let Some(language) = spoken_language(text) {
+                            languages.request(language, local_options(&settings));
+                        }
+                    },
+                );
+                send_recogniser_status(&recognisers, &mut backend_states, &event_loop);
                 let _ = visualiser_sender.send(AudioEvent::SendData(audio_buf));
             }
+            // the stream stopped, keep whatever was said last
+            recognisers.end_utterance(&tx);
+            send_transcriptions(
+                &rx,
+                &event_loop,
+                &text_processing,
+                &redactor,
+                &mut history,
+                |_| {},
+            );
         }
     });
 
     visualiser_handle
 }
 
+// keeps what has been learnt of the noise when only the strength changes
+#[cfg(feature = "graphical")]
+fn update_noise_suppression(
+    config: &Configuration,
+    stream_opts: &StreamOpts,
+    suppressor: &mut Option<NoiseSuppressor>,
+) {
+    let settings = config
+        .audio
+        .as_ref()
+        .map(|f| f.noise_suppression)
+        .unwrap_or_default();
+    match suppressor {
+        Some(suppressor) if settings.enabled => suppressor.set_strength(settings.strength),
+        Some(_) => *suppressor = None,
+        None if settings.enabled => {
+            debug!(strength = settings.strength, "suppressing noise");
+            *suppressor = Some(NoiseSuppressor::new(
+                stream_opts.sample_rate(),
+                stream_opts.channel_count(),
+                settings.strength,
+            ));
+        }
+        None => {}
+    }
+}
+
+// starts over whenever the settings change
+#[cfg(feature = "graphical")]
+fn update_gain_control(
+    config: &Configuration,
+    stream_opts: &StreamOpts,
+    gain_control: &mut Option<(GainControl, AutomaticGainControl)>,
+) {
+    let settings = config
+        .audio
+        .as_ref()
+        .map(|f| f.gain_control)
+        .unwrap_or_default();
+    if !settings.enabled {
+        *gain_control = None;
+    } else if gain_control.as_ref().map(|(f, _)| f) != Some(&settings) {
+        debug!(settings = ?settings, "controlling input gain");
+        *gain_control = Some((
+            settings,
+            AutomaticGainControl::new(
+                stream_opts.sample_rate(),
+                stream_opts.channel_count(),
+                settings.settings(),
+            ),
+        ));
+    }
+}
+
+// follows `command-grammar` across configuration reloads without reloading models
+#[cfg(feature = "graphical")]
+fn update_recognition_mode(
+    config: &Configuration,
+    current: &mut Option<PathBuf>,
+    recognisers: &mut SpeechRecognisers,
+    result_sender: &Sender<TranscriptionResult>,
+) {
+    let wanted = &config.speech_recognition.command_grammar;
+    if wanted == current {
+        return;
+    }
+    *current = wanted.clone();
+
+    let mode = match current {
+        Some(path) => RecognitionMode::from_grammar_file(path).unwrap_or_else(|e| {
+            error!("{e}");
+            RecognitionMode::Dictation
+        }),
+        None => RecognitionMode::Dictation,
+    };
+    debug!(grammar = ?current, "switching recognition mode");
+    recognisers.set_mode(mode, result_sender);
+}
+
+#[cfg(feature = "graphical")]
+fn send_transcriptions(
+    results: &Receiver<TranscriptionResult>,
+    event_loop: &Mutex<EventLoopProxy<KaraEvent>>,
+    text_processing: &TextProcessing,
+    redactor: &Redactor,
+    history: &mut Option<History>,
+    mut on_finalised: impl FnMut(&str),
+) {
+    for ev in results.try_iter() {
+        // spoken commands are matched against what was heard, not the written form
+        if ev.finalised() {
+            on_finalised(ev.transcription());
+        }
+        // history keeps what was shown
+        let ev = redactor.apply(text_processing.apply(ev));
+        if let Some(history) = history {
+            if let Err(e) = history.record(&ev) {
+                error!("{e}");
+            }
+        }
+        let proxy = event_loop.lock().unwrap();
+        let text = ev.transcription().to_owned();
+        let _ = proxy.send_event(if ev.finalised() {
+            KaraEvent::FinalisedSpeech(text)
+        } else {
+            KaraEvent::ReadingSpeech(text)
+        });
+    }
+}
+
+// trims the history to its retention policy every time it is opened
+#[cfg(feature = "graphical")]
+fn open_history(config: &config::History) -> Option<History> {
+    if !config.enabled {
+        return None;
+    }
+    let mut history = History::open(config.path())
+        .map_err(|e| error!("{e}"))
+        .ok()?;
+    match history.apply_retention(&config.retention) {
+        Ok(removed) if removed > 0 => debug!(removed, "old transcripts removed"),
+        Ok(_) => {}
+        Err(e) => error!("{e}"),
+    }
+    Some(history)
+}
+
+// only tells the ui when a backend changes state, not on every chunk
+#[cfg(feature = "graphical")]
+fn send_recogniser_status(
+    recognisers: &SpeechRecognisers,
+    last_states: &mut Vec<BackendState>,
+    event_loop: &Mutex<EventLoopProxy<KaraEvent>>,
+) {
+    let status = recognisers.status();
+    let states: Vec<_> = status.iter().map(|f| f.state).collect();
+    if states != *last_states {
+        *last_states = states;
+        let proxy = event_loop.lock().unwrap();
+        let _ = proxy.send_event(KaraEvent::RecogniserStatus(status));
+    }
+}
+
 pub fn get_audio_device_info(config: &Configuration) -> (Option<String>, Option<f32>) {
     match &config.audio {
         Some(audio) => (audio.input_device_name.clone(), audio.sample_rate),
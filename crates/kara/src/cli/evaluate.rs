@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use anyhow::bail;
+use asr::{
+    evaluation::{self, Corpus, Evaluation},
+    sources::Source,
+    TranscriptionError,
+};
+use tracing::warn;
+
+use crate::{audio::create_file_backend, config::file::read_config_file};
+
+/// Prints word and character error rates, real-time factor and latency for every configured
+/// backend. Results are compared as heard, text processing and redaction are not applied
+pub fn evaluate(directory: &Path, show_samples: bool) -> anyhow::Result<()> {
+    let (config, _) = read_config_file(None);
+    let corpus = Corpus::load(directory)?;
+    if corpus.is_empty() {
+        bail!("{} has no recordings with transcripts", directory.display());
+    }
+    let vocabulary = &config.speech_recognition.vocabulary;
+
+    println!(
+        "{:<32} {:>8} {:>8} {:>8} {:>8} {:>10}",
+        "source", "samples", "WER", "CER", "RTF", "latency"
+    );
+    for source in &config.speech_recognition.sources {
+        let label = label(source);
+        let evaluation = evaluation::evaluate(&corpus, |sample_rate| {
+            let backend = create_file_backend(source, sample_rate).ok_or_else(|| {
+                TranscriptionError::Configuration(format!("{label} could not be created"))
+            })?;
+            if !vocabulary.is_empty() {
+                if let Err(e) = backend.set_vocabulary(vocabulary) {
+                    warn!(source = label, "{e}");
+                }
+            }
+            Ok(backend)
+        });
+        print_evaluation(&label, &evaluation, show_samples);
+    }
+    Ok(())
+}
+
+fn print_evaluation(label: &str, evaluation: &Evaluation, show_samples: bool) {
+    println!(
+        "{label:<32} {:>8} {:>7.2}% {:>7.2}% {:>8.3} {:>7} ms",
+        evaluation.samples().len(),
+        evaluation.word_error_rate() * 100.0,
+        evaluation.character_error_rate() * 100.0,
+        evaluation.real_time_factor(),
+        evaluation.mean_latency().as_millis()
+    );
+    if evaluation.failures() > 0 {
+        println!("  {} recordings failed", evaluation.failures());
+    }
+    if show_samples {
+        for sample in evaluation.samples() {
+            println!(
+                "  {:<30} {:>17.2}% {:>7.2}% {:>8.3} {:>7} ms  {}",
+                sample.name(),
+                sample.word_error_rate() * 100.0,
+                sample.character_error_rate() * 100.0,
+                sample.real_time_factor(),
+                sample.latency().as_millis(),
+                sample.hypothesis()
+            );
+        }
+    }
+}
+
+// tells apart several sources of the same kind, such as two vosk models
+fn label(source: &Source) -> String {
+    match source {
+        Source::Kara {
+            model: Some(model), ..
+        } => format!("kara ({model})"),
+        Source::Kara { model_path, .. } => match model_path.file_name() {
+            Some(name) => format!("kara ({})", name.to_string_lossy()),
+            None => source.to_string(),
+        },
+        Source::VoskServer { url, .. } => format!("vosk-server ({url})"),
+        _ => source.to_string(),
+    }
+}
@@ -0,0 +1,65 @@
+use std::{sync::OnceLock, time::SystemTime};
+
+use asr::{
+    export::{export, ExportFormat, Segment},
+    history::{History, HistoryEntry, HistoryQuery},
+};
+use time::{macros::format_description, OffsetDateTime, UtcOffset};
+
+use crate::config::file::read_config_file;
+
+static LOCAL_OFFSET: OnceLock<UtcOffset> = OnceLock::new();
+
+/// Reads the offset transcripts are printed in. `time` only reads it while the process has a
+/// single thread, so this is called before the runtime starts
+pub fn read_local_offset() {
+    let _ = LOCAL_OFFSET.set(UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC));
+}
+
+/// Prints the stored transcripts that match, oldest first, or deletes them
+pub fn history(
+    query: &HistoryQuery,
+    delete: bool,
+    format: Option<ExportFormat>,
+) -> anyhow::Result<()> {
+    let (config, _) = read_config_file(None);
+    let mut history = History::open(config.history.path())?;
+    if delete {
+        let removed = history.delete(query)?;
+        println!("deleted {removed} transcripts");
+        return Ok(());
+    }
+    let entries = history.search(query)?;
+    match format {
+        Some(format) => print!("{}", export(&Segment::from_history(&entries), format)),
+        None => {
+            for entry in entries {
+                println!("{}", format_entry(&entry));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn format_entry(entry: &HistoryEntry) -> String {
+    let mut line = format!("[{}] ({}", local_time(entry.timestamp()), entry.source());
+    if let Some(confidence) = entry.confidence() {
+        line.push_str(&format!(" {confidence:.2}"));
+    }
+    line.push_str(") ");
+    if let Some(speaker) = entry.speaker() {
+        line.push_str(&format!("{speaker}: "));
+    }
+    line.push_str(entry.text());
+    line
+}
+
+fn local_time(time: SystemTime) -> String {
+    let offset = LOCAL_OFFSET.get().copied().unwrap_or(UtcOffset::UTC);
+    OffsetDateTime::from(time)
+        .to_offset(offset)
+        .format(format_description!(
+            "[year]-[month]-[day] [hour]:[minute]:[second]"
+        ))
+        .unwrap_or_default()
+}
@@ -1,6 +1,45 @@
-use crate::config::file::read_config_file;
+mod evaluate;
+mod history;
+mod transcribe;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use asr::history::HistoryQuery;
+
+use crate::config::{cli::Command, file::read_config_file};
+
+pub use evaluate::evaluate;
+pub use history::{history, read_local_offset};
+pub use transcribe::transcribe;
 
 pub async fn run() -> anyhow::Result<()> {
     let _config_file = read_config_file(None);
     std::process::exit(0);
 }
+
+pub fn run_command(command: &Command) -> anyhow::Result<()> {
+    match command {
+        Command::Transcribe { file, format } => transcribe(file, *format),
+        Command::Evaluate { corpus, samples } => evaluate(corpus, *samples),
+        Command::History {
+            search,
+            since,
+            until,
+            session,
+            limit,
+            delete,
+            export,
+        } => {
+            let ago = |age: Duration| SystemTime::now().checked_sub(age).unwrap_or(UNIX_EPOCH);
+            let query = HistoryQuery {
+                text: (!search.is_empty()).then(|| search.join(" ")),
+                since: since.map(ago),
+                until: until.map(ago),
+                session: session.clone(),
+                source: None,
+                limit: *limit,
+            };
+            history(&query, *delete, *export)
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use anyhow::bail;
+use asr::{
+    export::{export, ExportFormat, Segment},
+    recording::Recording,
+    text::Redactor,
+    TranscriptionResult,
+};
+use tracing::debug;
+
+use crate::{audio::create_file_recognisers, config::file::read_config_file};
+
+/// Prints the finalised segments of a recording, one per line unless another format is asked for
+pub fn transcribe(file: &Path, format: Option<ExportFormat>) -> anyhow::Result<()> {
+    let (config, _) = read_config_file(None);
+    let recording = Recording::open_wav(file)?;
+    debug!(
+        sample_rate = recording.sample_rate(),
+        duration = ?recording.duration(),
+        "transcribing recording"
+    );
+
+    let mut recognisers = create_file_recognisers(&config, recording.sample_rate());
+    if !recognisers.valid() {
+        bail!("no speech recognition backend could be created");
+    }
+    let text_processing = config.speech_recognition.text_processing;
+    let redactor = Redactor::new(&config.speech_recognition.redaction);
+    let segments: Vec<_> = recognisers
+        .transcribe_recording(&recording)?
+        .into_iter()
+        .map(|f| redactor.apply(text_processing.apply(f)))
+        .filter(|f| !f.transcription().is_empty())
+        .collect();
+    match format {
+        Some(format) => print!("{}", export(&Segment::from_results(&segments), format)),
+        None => {
+            for segment in segments {
+                println!("{}", format_segment(&segment));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn format_segment(segment: &TranscriptionResult) -> String {
+    let mut line = String::new();
+    if let (Some(first), Some(last)) = (segment.words().first(), segment.words().last()) {
+        line.push_str(&format!(
+            "[{} - {}] ",
+            timestamp(first.start()),
+            timestamp(last.end())
+        ));
+    }
+    if let Some(speaker) = segment.speaker() {
+        line.push_str(&format!("{speaker}: "));
+    }
+    line.push_str(segment.transcription());
+    line
+}
+
+// hh:mm:ss.ss
+fn timestamp(seconds: f32) -> String {
+    let hundredths = (seconds.max(0.0) * 100.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:02}",
+        hundredths / 360_000,
+        hundredths / 6_000 % 60,
+        hundredths / 100 % 60,
+        hundredths % 100
+    )
+}
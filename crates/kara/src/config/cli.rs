@@ -1,4 +1,7 @@
-use clap::{Parser, ValueEnum};
+use std::{path::PathBuf, time::Duration};
+
+use asr::export::ExportFormat;
+use clap::{Parser, Subcommand, ValueEnum};
 
 /// A digital assistant
 #[derive(Parser, Debug)]
@@ -8,6 +11,82 @@ pub struct Args {
     #[arg(short, long)]
     #[cfg(all(feature = "graphical", feature = "commandline"))]
     pub mode: Option<StartupMode>,
+
+    #[command(subcommand)]
+    #[cfg(feature = "commandline")]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Transcribe a WAV recording with the configured speech recognisers
+    Transcribe {
+        /// The recording to transcribe
+        file: PathBuf,
+
+        /// Print subtitles or JSON instead of plain lines: srt, vtt or json
+        #[arg(long)]
+        format: Option<ExportFormat>,
+    },
+    /// Score every configured speech recogniser against a corpus of WAV files, each with a
+    /// reference transcript in a .txt file of the same name
+    Evaluate {
+        /// Directory holding the corpus
+        corpus: PathBuf,
+
+        /// Also show how each recording went
+        #[arg(long)]
+        samples: bool,
+    },
+    /// Search, list or delete stored transcripts
+    History {
+        /// Words that all have to appear in a transcript
+        search: Vec<String>,
+
+        /// Only transcripts from within this long ago, such as 30m, 12h or 7d
+        #[arg(long, value_parser = parse_age)]
+        since: Option<Duration>,
+
+        /// Only transcripts from longer ago than this
+        #[arg(long, value_parser = parse_age)]
+        until: Option<Duration>,
+
+        /// Only transcripts from this session
+        #[arg(long)]
+        session: Option<String>,
+
+        /// Show at most this many of the most recent matches
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Delete the matching transcripts instead of showing them
+        #[arg(long, conflicts_with = "export")]
+        delete: bool,
+
+        /// Print the matches as subtitles or JSON: srt, vtt or json
+        #[arg(long)]
+        export: Option<ExportFormat>,
+    },
+}
+
+// a number followed by s, m, h or d
+fn parse_age(age: &str) -> Result<Duration, String> {
+    let split = age.find(|f: char| !f.is_ascii_digit()).unwrap_or(age.len());
+    let (amount, unit) = age.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("{age:?} does not start with a number"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("{unit:?} is not one of s, m, h or d")),
+    };
+    amount
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("{age:?} is too long ago"))
 }
 
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
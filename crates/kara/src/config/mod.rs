@@ -1,7 +1,15 @@
 pub mod file;
 pub mod watch;
 
-use asr::sources::Source;
+use std::{path::PathBuf, time::Duration};
+
+use asr::{
+    history::Retention,
+    sources::{Combination, Source},
+    text::{Redaction, TextProcessing},
+    vocabulary::Vocabulary,
+};
+use audio_utils::{gain::AgcSettings, vad::VadSettings};
 use clap::Parser;
 
 use serde::{Deserialize, Deserializer, Serialize};
@@ -23,6 +31,9 @@ pub struct Configuration {
     #[serde(default = "default_recogniser")]
     pub speech_recognition: SpeechRecognition,
 
+    #[serde(default)]
+    pub history: History,
+
     #[serde(default = "colours")]
     #[cfg(feature = "graphical")]
     pub colours: Colours,
@@ -72,6 +83,40 @@ fn default_recogniser() -> SpeechRecognition {
     SpeechRecognition::default()
 }
 
+/// Where finalised transcripts are kept and for how long. Nothing is kept unless enabled
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct History {
+    #[serde(default = "history_enabled")]
+    pub enabled: bool,
+
+    /// Defaults to `history.jsonl` in kara's data directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+
+    #[serde(default)]
+    pub retention: Retention,
+}
+
+fn history_enabled() -> bool {
+    false
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            enabled: history_enabled(),
+            path: None,
+            retention: Retention::default(),
+        }
+    }
+}
+
+impl History {
+    pub fn path(&self) -> PathBuf {
+        self.path.clone().unwrap_or_else(res_def::history_path)
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Audio {
     #[serde(rename = "input-device-name")]
@@ -80,11 +125,157 @@ pub struct Audio {
     #[serde(rename = "sample-rate")]
     pub sample_rate: Option<f32>,
 
+    #[serde(rename = "voice-activity")]
+    #[serde(default)]
+    pub voice_activity: VoiceActivityDetection,
+
+    #[serde(rename = "noise-suppression")]
+    #[serde(default)]
+    pub noise_suppression: NoiseSuppression,
+
+    #[serde(rename = "gain-control")]
+    #[serde(default)]
+    pub gain_control: GainControl,
+
     #[serde(default = "visualiser")]
     #[cfg(feature = "graphical")]
     pub visualiser: Visualiser,
 }
 
+/// Keeps silence away from the speech recognisers, each stretch of speech becomes an utterance
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct VoiceActivityDetection {
+    #[serde(default = "vad_enabled")]
+    pub enabled: bool,
+
+    /// Decibels above the background noise that count as speech
+    #[serde(default = "vad_threshold")]
+    pub threshold: f32,
+
+    /// Milliseconds of quiet before an utterance is over
+    #[serde(default = "vad_hangover")]
+    pub hangover: u64,
+}
+
+fn vad_enabled() -> bool {
+    true
+}
+
+fn vad_threshold() -> f32 {
+    VadSettings::default().threshold
+}
+
+fn vad_hangover() -> u64 {
+    VadSettings::default().hangover.as_millis() as u64
+}
+
+impl Default for VoiceActivityDetection {
+    fn default() -> Self {
+        Self {
+            enabled: vad_enabled(),
+            threshold: vad_threshold(),
+            hangover: vad_hangover(),
+        }
+    }
+}
+
+impl VoiceActivityDetection {
+    pub fn settings(&self) -> VadSettings {
+        VadSettings {
+            threshold: self.threshold,
+            hangover: Duration::from_millis(self.hangover),
+            ..VadSettings::default()
+        }
+    }
+}
+
+/// Takes steady background noise out of the microphone before recognition
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct NoiseSuppression {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// From 0, leaving the audio alone, to 1
+    #[serde(default = "noise_strength")]
+    pub strength: f32,
+}
+
+fn noise_strength() -> f32 {
+    0.5
+}
+
+impl Default for NoiseSuppression {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: noise_strength(),
+        }
+    }
+}
+
+/// Evens out the level of the microphone for recognition, the visualiser keeps its own
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct GainControl {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Level to bring speech to, in dBFS
+    #[serde(default = "agc_target")]
+    pub target: f32,
+
+    /// Milliseconds to turn down over when the input gets louder
+    #[serde(default = "agc_attack")]
+    pub attack: u64,
+
+    /// Milliseconds to turn back up over when it gets quieter
+    #[serde(default = "agc_release")]
+    pub release: u64,
+
+    /// The most the input is turned up by, in decibels
+    #[serde(rename = "max-gain")]
+    #[serde(default = "agc_max_gain")]
+    pub max_gain: f32,
+}
+
+fn agc_target() -> f32 {
+    AgcSettings::default().target
+}
+
+fn agc_attack() -> u64 {
+    AgcSettings::default().attack.as_millis() as u64
+}
+
+fn agc_release() -> u64 {
+    AgcSettings::default().release.as_millis() as u64
+}
+
+fn agc_max_gain() -> f32 {
+    AgcSettings::default().max_gain
+}
+
+impl Default for GainControl {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: agc_target(),
+            attack: agc_attack(),
+            release: agc_release(),
+            max_gain: agc_max_gain(),
+        }
+    }
+}
+
+impl GainControl {
+    pub fn settings(&self) -> AgcSettings {
+        AgcSettings {
+            target: self.target,
+            attack: Duration::from_millis(self.attack),
+            release: Duration::from_millis(self.release),
+            max_gain: self.max_gain,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Visualiser {
     #[serde(default = "default_loudness")]
@@ -178,7 +369,7 @@ fn default_loudness() -> f32 {
     1.5
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct SpeechRecognition {
     #[serde(default = "default_source")]
     #[serde(deserialize_with = "de_source_name_only")]
@@ -187,6 +378,27 @@ pub struct SpeechRecognition {
 
     #[serde(default = "sources")]
     pub sources: Vec<Source>,
+
+    #[serde(rename = "command-grammar")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command_grammar: Option<PathBuf>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ensemble: Option<Combination>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vocabulary::is_empty")]
+    pub vocabulary: Vocabulary,
+
+    #[serde(rename = "text-processing")]
+    #[serde(default)]
+    pub text_processing: TextProcessing,
+
+    #[serde(default)]
+    pub redaction: Redaction,
 }
 
 fn de_source_name_only<'de, D>(deserializer: D) -> Result<String, D::Error>
@@ -203,6 +415,12 @@ impl Default for SpeechRecognition {
         Self {
             default_source: Source::default().to_string(),
             sources: sources(),
+            command_grammar: None,
+            ensemble: None,
+            language: None,
+            vocabulary: Vocabulary::default(),
+            text_processing: TextProcessing::default(),
+            redaction: Redaction::default(),
         }
     }
 }
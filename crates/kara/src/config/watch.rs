@@ -5,16 +5,21 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+#[cfg(feature = "graphical")]
+use crossbeam_channel::Sender;
 #[cfg(feature = "graphical")]
 use iced_winit::winit::event_loop::EventLoopProxy;
 use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
 use tracing::{error, trace, warn};
 
-use crate::events::KaraEvent;
+use crate::{config::Configuration, events::KaraEvent};
 
 #[cfg(feature = "graphical")]
+/// Reloads the configuration when its file changes. The window is told with an event, the audio
+/// thread is handed each reloaded configuration over `updates`
 pub fn monitor_config(
     event_loop_proxy: Arc<Mutex<EventLoopProxy<KaraEvent>>>,
+    updates: Sender<Configuration>,
     path: Option<PathBuf>,
 ) {
     use res_def::dirs::config_dir;
@@ -22,7 +27,7 @@ pub fn monitor_config(
     if let Some(dir) = config_dir() {
         if let Some(path) = path {
             tokio::task::spawn_blocking(move || {
-                if let Err(e) = async_watch(path, event_loop_proxy, dir) {
+                if let Err(e) = async_watch(path, event_loop_proxy, updates, dir) {
                     error!("error: {:?}", e)
                 }
             });
@@ -53,6 +58,7 @@ fn async_watcher() -> notify::Result<(
 fn async_watch(
     path: impl AsRef<Path> + Debug,
     event_loop_proxy: Arc<Mutex<EventLoopProxy<KaraEvent>>>,
+    updates: Sender<Configuration>,
     dir: impl AsRef<Path> + Debug,
 ) -> notify::Result<()> {
     use super::file::read_config_file;
@@ -73,6 +79,7 @@ fn async_watch(
                     .any(|f| f.file_name() == path.as_ref().file_name())
                 {
                     let (config, _) = read_config_file(Some(path.as_ref().to_path_buf()));
+                    let _ = updates.send(config.clone());
 
                     let proxy = event_loop_proxy.lock().expect("could not get proxy lock");
                     if let Err(e) =
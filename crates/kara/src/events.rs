@@ -1,3 +1,6 @@
+use asr::sources::BackendStatus;
+use audio_utils::vad::VoiceActivity;
+
 use crate::config::Configuration;
 
 #[derive(Debug, Clone)]
@@ -9,4 +12,6 @@ pub enum KaraEvent {
     ReadingSpeech(String),
     FinalisedSpeech(String),
     UpdateProgressBar(f32),
+    RecogniserStatus(Vec<BackendStatus>),
+    VoiceActivity(VoiceActivity),
 }
@@ -3,7 +3,7 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use audio_utils::fft::{convert_buffer, merge_buffers};
+use audio_utils::fft::{merge_buffers_into, merge_latest, SpectrumAnalyser, SpectrumSettings};
 
 use crate::config::{Configuration, Visualiser};
 
@@ -27,6 +27,9 @@ pub fn visualise(
         let mut calculated_buffer = vec![];
         let mut smoothing_buffer = vec![];
         let mut smoothed_buffer = vec![];
+        // the oldest smoothing buffer is kept to be filled again rather than freed
+        let mut spare_buffer = vec![];
+        let mut analyser: Option<SpectrumAnalyser> = None;
 
         while let Ok(event) = event_receiver.recv() {
             match event {
@@ -38,22 +41,23 @@ pub fn visualise(
                     };
                     buffer.append(&mut b);
                     let resolution = config.resolution.into();
+                    // the fft is only planned again when the resolution changes
+                    if analyser.as_ref().is_some_and(|f| f.size() != resolution) {
+                        analyser = None;
+                    }
+                    let analyser =
+                        analyser.get_or_insert_with(|| SpectrumAnalyser::new(resolution));
+                    let settings = SpectrumSettings {
+                        max_frequency: 11025, // halved
+                        volume: config.loudness,
+                        frequency_scale_range: frequency_scale_range.clone(),
+                        smoothing_amount: config.smoothing_amount as u8,
+                        smoothing_size: config.smoothing_size as u8,
+                        frequency_scale_amount: 1,
+                    };
                     while buffer.len() > resolution {
-                        let c_b = convert_buffer(
-                            &buffer[0..resolution],
-                            11025, // halved
-                            config.loudness,
-                            &frequency_scale_range,
-                            config.smoothing_amount as u8,
-                            config.smoothing_size as u8,
-                            1,
-                        );
-
-                        calculated_buffer = if !calculated_buffer.is_empty() {
-                            merge_buffers(&[calculated_buffer, c_b])
-                        } else {
-                            c_b
-                        };
+                        let c_b = analyser.process(&buffer[0..resolution], &settings);
+                        merge_latest(&mut calculated_buffer, c_b);
                         // remove already calculated parts
                         buffer.drain(0..resolution);
                     }
@@ -72,15 +76,17 @@ pub fn visualise(
                         None => Visualiser::default(),
                     };
                     if !calculated_buffer.is_empty() {
-                        smoothing_buffer.push(calculated_buffer.clone());
+                        let mut b = std::mem::take(&mut spare_buffer);
+                        b.clone_from(&calculated_buffer);
+                        smoothing_buffer.push(b);
                     }
-                    smoothed_buffer = if !smoothing_buffer.is_empty() {
-                        merge_buffers(&smoothing_buffer)
+                    if !smoothing_buffer.is_empty() {
+                        merge_buffers_into(&smoothing_buffer, &mut smoothed_buffer);
                     } else {
-                        Vec::new()
-                    };
+                        smoothed_buffer.clear();
+                    }
                     while smoothing_buffer.len() > config.buffering.into() {
-                        smoothing_buffer.remove(0);
+                        spare_buffer = smoothing_buffer.remove(0);
                     }
                 }
             }
@@ -10,6 +10,9 @@ use iced_winit::{
 use palette::Srgb;
 use tracing::error;
 
+use asr::sources::{BackendState, BackendStatus};
+use audio_utils::vad::VoiceActivity;
+
 use crate::{config::Configuration, events::KaraEvent};
 
 pub struct Controls {
@@ -17,6 +20,9 @@ pub struct Controls {
     foreground_color: Color,
     padding: u16,
     text: String,
+    // backends that are not healthy, shown under the transcription
+    recogniser_status: Option<String>,
+    voice_activity: VoiceActivity,
     font_size: u16,
     progress_bar: ProgressBarData,
 }
@@ -101,6 +107,8 @@ impl Controls {
                 a: opacity,
             },
             text: String::from("Hello there!"),
+            recogniser_status: None,
+            voice_activity: VoiceActivity::Silence,
             foreground_color: Color {
                 r: fg_r,
                 g: fg_g,
@@ -166,20 +174,38 @@ impl Program for Controls {
             KaraEvent::UpdateProgressBar(new_progress) => {
                 self.progress_bar.update_progress(new_progress);
             }
+            KaraEvent::RecogniserStatus(status) => {
+                self.recogniser_status = describe_status(&status);
+            }
+            KaraEvent::VoiceActivity(activity) => self.voice_activity = activity,
             _ => {}
         }
         Command::none()
     }
 
     fn view(&self) -> Element<KaraEvent, Renderer> {
-        let content = Column::new()
-            .spacing(100)
+        let mut speech = Column::new()
+            .spacing(2)
             .push(
                 Text::new(&self.text)
                     .style(self.foreground_colour())
                     .size(self.font_size),
             )
             .align_items(iced_winit::Alignment::Center);
+        // dimmed while nobody is speaking
+        let mut indicator = self.foreground_colour();
+        if self.voice_activity == VoiceActivity::Silence {
+            indicator.a = 0.3;
+        }
+        let status = match &self.recogniser_status {
+            Some(status) => format!("\u{25CF} {status}"),
+            None => String::from("\u{25CF}"),
+        };
+        speech = speech.push(Text::new(status).style(indicator).size(self.font_size / 2));
+        let content = Column::new()
+            .spacing(100)
+            .push(speech)
+            .align_items(iced_winit::Alignment::Center);
         let style: Box<dyn StyleSheet<Style = Theme>> = Box::new(MyProgressbarStyle {
             background: self.progress_bar.background_color,
             bar: self.progress_bar.foreground_color,
@@ -208,6 +234,15 @@ impl Program for Controls {
     }
 }
 
+fn describe_status(status: &[BackendStatus]) -> Option<String> {
+    let unhealthy: Vec<_> = status
+        .iter()
+        .filter(|f| f.state != BackendState::Healthy)
+        .map(|f| format!("{}: {}", f.source, f.state))
+        .collect();
+    (!unhealthy.is_empty()).then(|| unhealthy.join(", "))
+}
+
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum ColourType {
     Background,
@@ -41,7 +41,8 @@ pub async fn run() -> anyhow::Result<()> {
     let event_loop_proxy = Arc::new(Mutex::new(event_loop.create_proxy()));
 
     let (config_file, path) = read_config_file(None);
-    crate::config::watch::monitor_config(Arc::clone(&event_loop_proxy), path);
+    let (config_sender, config_updates) = crossbeam_channel::unbounded();
+    crate::config::watch::monitor_config(Arc::clone(&event_loop_proxy), config_sender, path);
     let (device_name, sample_rate) = get_audio_device_info(&config_file);
 
     let window_settings = &config_file.window;
@@ -69,6 +70,7 @@ pub async fn run() -> anyhow::Result<()> {
         stream_opts,
         Arc::clone(&config_file),
         Arc::clone(&event_loop_proxy),
+        config_updates,
         speech_recognisers,
     );
 
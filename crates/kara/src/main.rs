@@ -10,12 +10,22 @@ mod logger;
 
 fn main() {
     logger::initialise_logger().unwrap();
+    #[cfg(feature = "commandline")]
+    cli::read_local_offset();
     start();
 }
 
 #[tokio::main]
 async fn start() {
     let args = config::initialise_application();
+    #[cfg(feature = "commandline")]
+    if let Some(command) = &args.command {
+        if let Err(e) = cli::run_command(command) {
+            tracing::error!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
     #[cfg(all(feature = "graphical", feature = "commandline"))]
     match args.mode.unwrap_or_default() {
         config::cli::StartupMode::Gui => {
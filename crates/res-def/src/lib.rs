@@ -1,3 +1,5 @@
+pub mod models;
+
 use dirs::data_dir;
 use std::path::PathBuf;
 
@@ -15,10 +17,26 @@ mod tests {
         assert!(Url::has_host(&url));
         Ok(())
     }
+
+    #[test]
+    fn catalogue_entries_are_distinct() {
+        let catalogue = models::catalogue();
+        for (i, entry) in catalogue.iter().enumerate() {
+            assert!(Url::parse(entry.url).is_ok());
+            assert!(catalogue[i + 1..].iter().all(|f| f.id != entry.id));
+            assert_ne!(entry.install_path(), model_path());
+        }
+        assert!(models::find_model(models::DEFAULT_MODEL).is_some());
+        assert_eq!(models::language_tag(" German"), Some("de"));
+        assert_eq!(models::language_tag("klingon"), None);
+    }
 }
 
 pub fn vosk_model_url() -> String {
-    "https://alphacephei.com/vosk/models/vosk-model-small-en-us-0.15.zip".to_owned()
+    models::find_model(models::DEFAULT_MODEL)
+        .expect("the default model is in the catalogue")
+        .url
+        .to_owned()
 }
 
 pub fn model_path() -> PathBuf {
@@ -27,4 +45,18 @@ pub fn model_path() -> PathBuf {
     data_dir
 }
 
+/// Named voice profiles used to tell speakers apart
+pub fn speaker_profiles_path() -> PathBuf {
+    let mut path = model_path();
+    path.push("speakers");
+    path
+}
+
+/// Finalised transcripts, one JSON entry per line
+pub fn history_path() -> PathBuf {
+    let mut data_dir = data_dir().unwrap_or_default();
+    data_dir.push("kara/history.jsonl");
+    data_dir
+}
+
 pub use dirs;
@@ -0,0 +1,179 @@
+use std::path::PathBuf;
+
+use crate::model_path;
+
+/// The model downloaded when nothing else is configured
+pub const DEFAULT_MODEL: &str = "vosk-model-small-en-us-0.15";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelSize {
+    /// Tens of megabytes, meant for desktops and small devices
+    Small,
+    /// Gigabytes, more accurate but slow to load and hungry for memory
+    Large,
+}
+
+/// A speech recognition model kara knows how to fetch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelEntry {
+    pub id: &'static str,
+    /// BCP 47 language tag
+    pub language: &'static str,
+    pub size: ModelSize,
+    pub url: &'static str,
+    pub licence: &'static str,
+    pub sample_rate: u32,
+}
+
+impl ModelEntry {
+    /// Every model is extracted into its own directory so several can be installed at once
+    pub fn install_path(&self) -> PathBuf {
+        let mut path = model_path();
+        path.push(self.id);
+        path
+    }
+
+    pub fn is_installed(&self) -> bool {
+        self.install_path().is_dir()
+    }
+}
+
+const fn vosk(
+    id: &'static str,
+    language: &'static str,
+    size: ModelSize,
+    url: &'static str,
+) -> ModelEntry {
+    ModelEntry {
+        id,
+        language,
+        size,
+        url,
+        licence: "Apache-2.0",
+        sample_rate: 16000,
+    }
+}
+
+const CATALOGUE: &[ModelEntry] = &[
+    vosk(
+        "vosk-model-small-en-us-0.15",
+        "en-US",
+        ModelSize::Small,
+        "https://alphacephei.com/vosk/models/vosk-model-small-en-us-0.15.zip",
+    ),
+    vosk(
+        "vosk-model-en-us-0.22",
+        "en-US",
+        ModelSize::Large,
+        "https://alphacephei.com/vosk/models/vosk-model-en-us-0.22.zip",
+    ),
+    vosk(
+        "vosk-model-small-en-in-0.4",
+        "en-IN",
+        ModelSize::Small,
+        "https://alphacephei.com/vosk/models/vosk-model-small-en-in-0.4.zip",
+    ),
+    vosk(
+        "vosk-model-small-de-0.15",
+        "de",
+        ModelSize::Small,
+        "https://alphacephei.com/vosk/models/vosk-model-small-de-0.15.zip",
+    ),
+    vosk(
+        "vosk-model-small-fr-0.22",
+        "fr",
+        ModelSize::Small,
+        "https://alphacephei.com/vosk/models/vosk-model-small-fr-0.22.zip",
+    ),
+    vosk(
+        "vosk-model-small-es-0.42",
+        "es",
+        ModelSize::Small,
+        "https://alphacephei.com/vosk/models/vosk-model-small-es-0.42.zip",
+    ),
+    vosk(
+        "vosk-model-small-it-0.22",
+        "it",
+        ModelSize::Small,
+        "https://alphacephei.com/vosk/models/vosk-model-small-it-0.22.zip",
+    ),
+    vosk(
+        "vosk-model-small-pt-0.3",
+        "pt",
+        ModelSize::Small,
+        "https://alphacephei.com/vosk/models/vosk-model-small-pt-0.3.zip",
+    ),
+    vosk(
+        "vosk-model-small-ru-0.22",
+        "ru",
+        ModelSize::Small,
+        "https://alphacephei.com/vosk/models/vosk-model-small-ru-0.22.zip",
+    ),
+    vosk(
+        "vosk-model-small-cn-0.22",
+        "zh",
+        ModelSize::Small,
+        "https://alphacephei.com/vosk/models/vosk-model-small-cn-0.22.zip",
+    ),
+    vosk(
+        "vosk-model-small-ja-0.22",
+        "ja",
+        ModelSize::Small,
+        "https://alphacephei.com/vosk/models/vosk-model-small-ja-0.22.zip",
+    ),
+    vosk(
+        "vosk-model-small-hi-0.22",
+        "hi",
+        ModelSize::Small,
+        "https://alphacephei.com/vosk/models/vosk-model-small-hi-0.22.zip",
+    ),
+];
+
+pub fn catalogue() -> &'static [ModelEntry] {
+    CATALOGUE
+}
+
+pub fn find_model(id: &str) -> Option<&'static ModelEntry> {
+    CATALOGUE.iter().find(|f| f.id == id)
+}
+
+pub fn installed_models() -> impl Iterator<Item = &'static ModelEntry> {
+    CATALOGUE.iter().filter(|f| f.is_installed())
+}
+
+/// The first installed model for a language, an exact tag wins over one that only shares the
+/// primary language so `en` can pick `en-US`
+pub fn installed_model_for(language: &str) -> Option<&'static ModelEntry> {
+    let primary = |tag: &str| tag.split('-').next().unwrap_or(tag).to_lowercase();
+    let installed: Vec<_> = installed_models().collect();
+    installed
+        .iter()
+        .find(|f| f.language.eq_ignore_ascii_case(language))
+        .or_else(|| {
+            installed
+                .iter()
+                .find(|f| primary(f.language) == primary(language))
+        })
+        .copied()
+}
+
+const LANGUAGE_NAMES: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("german", "de"),
+    ("french", "fr"),
+    ("spanish", "es"),
+    ("italian", "it"),
+    ("portuguese", "pt"),
+    ("russian", "ru"),
+    ("chinese", "zh"),
+    ("japanese", "ja"),
+    ("hindi", "hi"),
+];
+
+/// Turns a spoken language name such as "german" into its tag
+pub fn language_tag(name: &str) -> Option<&'static str> {
+    LANGUAGE_NAMES
+        .iter()
+        .find(|(f, _)| f.eq_ignore_ascii_case(name.trim()))
+        .map(|(_, tag)| *tag)
+}
@@ -11,10 +11,11 @@ use reqwest::{
     header::{ACCEPT_RANGES, CONTENT_LENGTH, RANGE},
     Client, StatusCode,
 };
-use res_def::model_path;
+use res_def::{model_path, models::ModelEntry};
+use sha2::{Digest, Sha256};
 use tokio::{
     fs::{create_dir_all, File, OpenOptions},
-    io::{AsyncSeekExt, AsyncWriteExt},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
 };
 use tracing::{debug, error, info, trace};
 use zip::ZipArchive;
@@ -24,6 +25,7 @@ type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>
 struct VoskModel {
     url: String,
     destination: PathBuf,
+    checksum: Option<String>,
     progress: crossbeam_channel::Receiver<f32>,
 }
 
@@ -47,12 +49,24 @@ impl ResGet {
             vosk_model: VoskModel {
                 url: url.to_string(),
                 destination,
+                checksum: None,
                 progress: rx,
             },
             progress_sender: tx,
         }
     }
 
+    /// Downloads a catalogue model into its own directory
+    pub fn for_model(model: &ModelEntry) -> Self {
+        Self::new(model.url, model.install_path())
+    }
+
+    /// Checks the archive against a SHA-256 digest before extracting it
+    pub fn with_checksum(mut self, checksum: &str) -> Self {
+        self.vosk_model.checksum = Some(checksum.to_owned());
+        self
+    }
+
     pub fn get_progress(&self) -> &crossbeam_channel::Receiver<f32> {
         &self.vosk_model.progress
     }
@@ -123,6 +137,7 @@ impl ResGet {
                         file,
                         path_buf.parent().ok_or("no parent")?,
                         &path_buf.display().to_string(),
+                        self.vosk_model.checksum.as_deref(),
                     )
                     .await?;
                 } else {
@@ -132,6 +147,7 @@ impl ResGet {
                         &path_buf,
                         url,
                         file_name,
+                        self.vosk_model.checksum.as_deref(),
                         &self.progress_sender,
                     )
                     .await?;
@@ -144,6 +160,7 @@ impl ResGet {
                     &path_buf,
                     url,
                     file_name,
+                    self.vosk_model.checksum.as_deref(),
                     &self.progress_sender,
                 )
                 .await?;
@@ -162,6 +179,7 @@ async fn download_no_resume(
     path_buf: &Path,
     url: &str,
     file_name: &str,
+    checksum: Option<&str>,
     progress_sender: &crossbeam_channel::Sender<f32>,
 ) -> Result<()> {
     trace!(file_name = file_name, "starting no resume download");
@@ -197,6 +215,7 @@ async fn download_no_resume(
         file,
         path_buf.parent().ok_or("no parent")?,
         &path_buf.display().to_string(),
+        checksum,
     )
     .await?;
 
@@ -243,7 +262,39 @@ impl Iterator for PartialRangeIter {
     }
 }
 
-async fn extract_file(file: File, base_parent: &Path, file_name: &str) -> Result<()> {
+// a corrupt or tampered archive is removed so the next attempt starts over
+async fn verify_checksum(file_name: &str, checksum: &str) -> Result<()> {
+    // models run to gigabytes, so they are hashed a piece at a time
+    let mut file = File::open(file_name).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    let digest = format!("{:x}", hasher.finalize());
+    if !digest.eq_ignore_ascii_case(checksum) {
+        tokio::fs::remove_file(file_name).await?;
+        Err(format!(
+            "checksum mismatch for {file_name}, expected {checksum} but got {digest}"
+        ))?;
+    }
+    trace!(file = file_name, "checksum verified");
+    Ok(())
+}
+
+async fn extract_file(
+    file: File,
+    base_parent: &Path,
+    file_name: &str,
+    checksum: Option<&str>,
+) -> Result<()> {
+    if let Some(checksum) = checksum {
+        verify_checksum(file_name, checksum).await?;
+    }
     trace!(file = file_name, "attempting extraction");
     use gag::Gag;
     let _err_gag = Gag::stderr()?;
@@ -11,3 +11,20 @@ async fn verify_remote_model() -> Result<(), Box<dyn std::error::Error>> {
     assert_eq!(StatusCode::OK, response.status());
     Ok(())
 }
+
+#[tokio::test]
+async fn mismatched_archive_is_removed() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("kara-checksum-{}.zip", std::process::id()));
+    let file_name = path.display().to_string();
+
+    tokio::fs::write(&path, b"kara").await?;
+    let digest = "a5fbbc9ddc5c4dd9f9e66e7ed93a3fbc1f7f7a1f53a7d0a2bc88f35b5b3a8e3a";
+    assert!(super::verify_checksum(&file_name, digest).await.is_err());
+    assert!(!path.exists());
+
+    tokio::fs::write(&path, b"kara").await?;
+    let digest = format!("{:x}", <sha2::Sha256 as sha2::Digest>::digest(b"kara"));
+    assert!(super::verify_checksum(&file_name, &digest).await.is_ok());
+    tokio::fs::remove_file(&path).await?;
+    Ok(())
+}